@@ -1,12 +1,14 @@
 use {
-    anyhow::Result,
+    crate::indexer::Tracer,
+    anyhow::{bail, ensure, Result},
     ethrpc::types::{ArrayVec, LogFilterValue},
     serde::Deserialize,
     solabi::{
-        abi::EventDescriptor,
+        abi::{EventDescriptor, FunctionDescriptor},
         ethprim::{Address, Digest},
     },
     std::{
+        collections::{BTreeMap, HashMap, HashSet},
         fmt::{self, Debug, Formatter},
         fs,
         path::{Path, PathBuf},
@@ -18,12 +20,51 @@ use {
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
-    pub ethrpc: Url,
+    #[serde(default, with = "endpoints")]
+    pub ethrpc: Vec<Endpoint>,
     pub database: Database,
     #[serde(default = "indexer::default")]
     pub indexer: Indexer,
-    #[serde(rename = "event")]
+    #[serde(default, rename = "event")]
     pub events: Vec<Event>,
+    #[serde(default, rename = "call")]
+    pub calls: Vec<Call>,
+    #[serde(default, rename = "transfer")]
+    pub transfers: Vec<Transfer>,
+    #[serde(default, rename = "snapshot")]
+    pub snapshots: Vec<Snapshot>,
+    #[serde(default, rename = "network")]
+    pub networks: Vec<Network>,
+}
+
+/// A named network with its own node endpoints, events, calls, transfers and
+/// snapshots. These are stored in the shared database with the network name as
+/// table prefix. The network's `indexer` settings replace the global ones.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Network {
+    pub name: String,
+    #[serde(with = "endpoints")]
+    pub ethrpc: Vec<Endpoint>,
+    #[serde(default)]
+    pub indexer: Option<Indexer>,
+    #[serde(default, rename = "event")]
+    pub events: Vec<Event>,
+    #[serde(default, rename = "call")]
+    pub calls: Vec<Call>,
+    #[serde(default, rename = "transfer")]
+    pub transfers: Vec<Transfer>,
+    #[serde(default, rename = "snapshot")]
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// A node endpoint. Endpoints with lower `priority` values are preferred.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Endpoint {
+    pub url: Url,
+    #[serde(default)]
+    pub priority: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,13 +74,50 @@ pub enum Database {
     Postgres { connection: String },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Indexer {
     #[serde(default = "indexer::default_page_size")]
     pub page_size: u64,
+    #[serde(default = "indexer::default_concurrency")]
+    pub concurrency: usize,
     #[serde(default = "indexer::default_poll_interval", with = "duration")]
     pub poll_interval: Duration,
+    #[serde(default = "indexer::default_finality")]
+    pub finality: Finality,
+    #[serde(default = "retry::default")]
+    pub retry: Retry,
+    #[serde(default)]
+    pub cross_check: bool,
+    #[serde(default)]
+    pub websocket: Option<Url>,
+    #[serde(default)]
+    pub fatal_decode_errors: bool,
+    /// The node method to trace blocks with, for indexing internal calls and
+    /// value transfers.
+    #[serde(default)]
+    pub traces: Option<Tracer>,
+}
+
+/// How to determine which blocks are final. Either `"finalized"`, `"safe"` or
+/// `{ confirmations = N }` for the latest block minus `N` confirmations.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Finality {
+    Finalized,
+    Safe,
+    Confirmations(u64),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Retry {
+    #[serde(default = "retry::default_initial_backoff", with = "duration")]
+    pub initial_backoff: Duration,
+    #[serde(default = "retry::default_max_backoff", with = "duration")]
+    pub max_backoff: Duration,
+    #[serde(default)]
+    pub max_retries: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -47,19 +125,119 @@ pub struct Event {
     pub name: String,
     #[serde(default)]
     pub start: u64,
+    #[serde(default)]
+    pub end: Option<u64>,
     pub contract: Contract,
     #[serde(default)]
-    pub topics: ArrayVec<LogFilterValue<Digest>, 3>,
+    pub topics: ArrayVec<LogFilterValue<Digest>, 4>,
+    #[serde(default)]
+    pub filter: BTreeMap<String, toml::Value>,
     #[serde(with = "signature")]
     pub signature: EventDescriptor,
+    /// Whether to store the receipts of the transactions that emitted the
+    /// event in the `_transactions` table.
+    #[serde(default)]
+    pub receipt: bool,
+}
+
+/// A function to index calls for. Calls are decoded from the calldata of
+/// transactions to the configured contracts and stored along with the
+/// transaction's sender, value and success status. With `internal`, calls
+/// made by other contracts are indexed as well, which requires `traces`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Call {
+    pub name: String,
+    #[serde(default)]
+    pub start: u64,
+    #[serde(default)]
+    pub end: Option<u64>,
+    pub contract: Contract,
+    #[serde(with = "function")]
+    pub signature: FunctionDescriptor,
+    #[serde(default)]
+    pub internal: bool,
 }
 
+/// Value transfers from or to the configured contracts, including internal
+/// transfers made by contracts. This requires `traces`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Transfer {
+    pub name: String,
+    #[serde(default)]
+    pub start: u64,
+    #[serde(default)]
+    pub end: Option<u64>,
+    pub contract: Contract,
+}
+
+/// A view function to call every `interval` blocks, starting at `start`. The
+/// function is called with the same `arguments` on each of the configured
+/// contracts, and its return values are stored like event fields with the
+/// contract's position in the contract list as log index.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    #[serde(default)]
+    pub start: u64,
+    #[serde(default)]
+    pub end: Option<u64>,
+    pub contract: Contract,
+    #[serde(with = "function")]
+    pub signature: FunctionDescriptor,
+    #[serde(default)]
+    pub arguments: Vec<toml::Value>,
+    pub interval: u64,
+}
+
+/// The contracts to index an event for. Either `"*"` for all contracts, a
+/// single address, a list of addresses, a map of labels to addresses, or the
+/// contracts created by a factory event.
 #[derive(Debug, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum Contract {
     #[serde(with = "contract")]
     All,
     Address(Address),
+    Addresses(Vec<Address>),
+    Factory(Factory),
+    Labeled(BTreeMap<String, Address>),
+}
+
+/// Contracts discovered from the address `field` of the factory event named
+/// `from_event`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Factory {
+    pub from_event: String,
+    pub field: String,
+}
+
+impl Contract {
+    /// Returns the configured contract addresses in order, or `None` for all
+    /// contracts, and collects the labels of labeled contracts. Adapters that
+    /// support factory-discovered contracts handle them before calling this.
+    pub fn addresses(self, labels: &mut HashMap<Address, String>) -> Result<Option<Vec<Address>>> {
+        Ok(match self {
+            Contract::All => None,
+            Contract::Address(address) => Some(vec![address]),
+            Contract::Addresses(addresses) => {
+                ensure!(!addresses.is_empty(), "empty contract address list");
+                Some(addresses)
+            }
+            Contract::Labeled(contracts) => {
+                ensure!(!contracts.is_empty(), "empty contract address map");
+                let addresses = contracts.values().copied().collect();
+                for (label, address) in contracts {
+                    ensure!(
+                        labels.insert(address, label).is_none(),
+                        "duplicate contract address {address}"
+                    );
+                }
+                Some(addresses)
+            }
+            Contract::Factory(_) => bail!("factory-discovered contracts are not supported"),
+        })
+    }
 }
 
 impl Config {
@@ -67,26 +245,227 @@ impl Config {
     /// configuration and its root path.
     pub fn load(path: &Path) -> Result<(Self, PathBuf)> {
         let toml = fs::read_to_string(path)?;
-        let config = toml::from_str(&toml)?;
+        let config = toml::from_str::<Self>(&toml)?;
+        config.validate()?;
         let root = fs::canonicalize(path)?
             .parent()
             .expect("file path without a parent")
             .to_owned();
         Ok((config, root))
     }
+
+    fn validate(&self) -> Result<()> {
+        let unnamed = self.unnamed();
+        ensure!(
+            !unnamed || !self.ethrpc.is_empty(),
+            "events, calls, transfers and snapshots without a network require a top-level \
+             `ethrpc`"
+        );
+        ensure!(
+            unnamed || !self.networks.is_empty(),
+            "no events, calls, transfers, snapshots or networks configured"
+        );
+        let mut names = HashSet::new();
+        for network in &self.networks {
+            // Network names prefix table names, so they must be valid SQL
+            // identifiers on their own.
+            ensure!(
+                network
+                    .name
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_alphabetic())
+                    && network
+                        .name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "invalid network name '{}'; network names must start with a letter and only \
+                 contain letters, digits and underscores",
+                network.name
+            );
+            ensure!(
+                names.insert(&network.name),
+                "duplicate network {}",
+                network.name
+            );
+        }
+        // Identifiers are case-insensitive in both SQLite and Postgres.
+        let mut tables = HashSet::new();
+        for network in self.networks() {
+            for name in network.names() {
+                ensure!(
+                    tables.insert(name.to_ascii_lowercase()),
+                    "duplicate table name {name}; names of events, calls, transfers and \
+                     snapshots must be unique after prefixing them with their network name"
+                );
+            }
+        }
+        for network in self.networks() {
+            let traced =
+                network.calls.iter().any(|call| call.internal) || !network.transfers.is_empty();
+            let indexer = network.indexer.as_ref().unwrap_or(&self.indexer);
+            ensure!(
+                !traced || indexer.traces.is_some(),
+                "internal calls and transfers require `traces` indexer setting"
+            );
+
+            let bounds = network
+                .events
+                .iter()
+                .map(|event| (&event.name, event.start, event.end))
+                .chain(network.calls.iter().map(|c| (&c.name, c.start, c.end)))
+                .chain(network.transfers.iter().map(|t| (&t.name, t.start, t.end)))
+                .chain(network.snapshots.iter().map(|s| (&s.name, s.start, s.end)));
+            for (name, start, end) in bounds {
+                ensure!(
+                    end.map_or(true, |end| end >= start),
+                    "{name} ends before its start block"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the networks to index. Events, calls, transfers and snapshots
+    /// configured outside of a named network are indexed on an unnamed network
+    /// using the top-level `ethrpc` endpoints.
+    pub fn networks(&self) -> Vec<Network> {
+        let mut networks = Vec::new();
+        if self.unnamed() {
+            networks.push(Network {
+                name: String::new(),
+                ethrpc: self.ethrpc.clone(),
+                indexer: None,
+                events: self.events.clone(),
+                calls: self.calls.clone(),
+                transfers: self.transfers.clone(),
+                snapshots: self.snapshots.clone(),
+            });
+        }
+        networks.extend(self.networks.iter().cloned());
+        networks
+    }
+
+    /// Returns `true` if anything is configured outside of a named network.
+    fn unnamed(&self) -> bool {
+        !self.events.is_empty()
+            || !self.calls.is_empty()
+            || !self.transfers.is_empty()
+            || !self.snapshots.is_empty()
+    }
+}
+
+impl Network {
+    /// Returns the network's events with their database names. Events of
+    /// named networks are prefixed with the network name, which also applies
+    /// to the factory events they reference.
+    pub fn events(&self) -> Vec<Event> {
+        self.events
+            .iter()
+            .cloned()
+            .map(|mut event| {
+                event.name = self.table_name(&event.name);
+                if let Contract::Factory(factory) = &mut event.contract {
+                    factory.from_event = self.table_name(&factory.from_event);
+                }
+                event
+            })
+            .collect()
+    }
+
+    /// Returns the network's calls with their database names.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls
+            .iter()
+            .cloned()
+            .map(|mut call| {
+                call.name = self.table_name(&call.name);
+                call
+            })
+            .collect()
+    }
+
+    /// Returns the network's transfers with their database names.
+    pub fn transfers(&self) -> Vec<Transfer> {
+        self.transfers
+            .iter()
+            .cloned()
+            .map(|mut transfer| {
+                transfer.name = self.table_name(&transfer.name);
+                transfer
+            })
+            .collect()
+    }
+
+    /// Returns the network's snapshots with their database names.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.snapshots
+            .iter()
+            .cloned()
+            .map(|mut snapshot| {
+                snapshot.name = self.table_name(&snapshot.name);
+                snapshot
+            })
+            .collect()
+    }
+
+    /// Returns the database names of the network's events, calls, transfers
+    /// and snapshots.
+    pub fn names(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .map(|event| &event.name)
+            .chain(self.calls.iter().map(|call| &call.name))
+            .chain(self.transfers.iter().map(|transfer| &transfer.name))
+            .chain(self.snapshots.iter().map(|snapshot| &snapshot.name))
+            .map(|name| self.table_name(name))
+            .collect()
+    }
+
+    fn table_name(&self, name: &str) -> String {
+        match self.name.as_str() {
+            "" => name.to_string(),
+            network => format!("{network}_{name}"),
+        }
+    }
 }
 
 impl Debug for Config {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct("Config")
-            .field("ethrpc", &self.ethrpc.as_str())
+            .field("ethrpc", &urls(&self.ethrpc))
             .field("database", &self.database)
             .field("indexer", &self.indexer)
             .field("event", &self.events)
+            .field("call", &self.calls)
+            .field("transfer", &self.transfers)
+            .field("snapshot", &self.snapshots)
+            .field("network", &self.networks)
+            .finish()
+    }
+}
+
+impl Debug for Network {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Network")
+            .field("name", &self.name)
+            .field("ethrpc", &urls(&self.ethrpc))
+            .field("indexer", &self.indexer)
+            .field("event", &self.events)
+            .field("call", &self.calls)
+            .field("transfer", &self.transfers)
+            .field("snapshot", &self.snapshots)
             .finish()
     }
 }
 
+fn urls(endpoints: &[Endpoint]) -> Vec<&str> {
+    endpoints
+        .iter()
+        .map(|endpoint| endpoint.url.as_str())
+        .collect()
+}
+
 mod signature {
     use {
         serde::{de, Deserialize, Deserializer},
@@ -103,6 +482,54 @@ mod signature {
     }
 }
 
+mod function {
+    use {
+        serde::{de, Deserialize, Deserializer},
+        solabi::abi::FunctionDescriptor,
+        std::borrow::Cow,
+    };
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<FunctionDescriptor, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = Cow::<str>::deserialize(deserializer)?;
+        FunctionDescriptor::parse_declaration(s.as_ref()).map_err(de::Error::custom)
+    }
+}
+
+mod endpoints {
+    use {
+        super::Endpoint,
+        serde::{de, Deserialize, Deserializer},
+        url::Url,
+    };
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Endpoints {
+        Single(Url),
+        Multiple(Vec<Endpoint>),
+    }
+
+    /// Deserializes either a single URL or a list of endpoints, returning the
+    /// endpoints ordered by priority.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Endpoint>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut endpoints = match Endpoints::deserialize(deserializer)? {
+            Endpoints::Single(url) => vec![Endpoint { url, priority: 0 }],
+            Endpoints::Multiple(endpoints) => endpoints,
+        };
+        if endpoints.is_empty() {
+            return Err(de::Error::custom("expected at least one endpoint"));
+        }
+        endpoints.sort_by_key(|endpoint| endpoint.priority);
+        Ok(endpoints)
+    }
+}
+
 mod contract {
     use {
         serde::{de, Deserialize, Deserializer},
@@ -121,12 +548,22 @@ mod contract {
 }
 
 mod indexer {
-    use {super::Indexer, std::time::Duration};
+    use {
+        super::{Finality, Indexer},
+        std::time::Duration,
+    };
 
     pub fn default() -> Indexer {
         Indexer {
             page_size: default_page_size(),
+            concurrency: default_concurrency(),
             poll_interval: default_poll_interval(),
+            finality: default_finality(),
+            retry: super::retry::default(),
+            cross_check: false,
+            websocket: None,
+            fatal_decode_errors: false,
+            traces: None,
         }
     }
 
@@ -134,9 +571,37 @@ mod indexer {
         1000
     }
 
+    pub fn default_concurrency() -> usize {
+        1
+    }
+
     pub fn default_poll_interval() -> Duration {
         Duration::from_secs_f64(0.1)
     }
+
+    pub fn default_finality() -> Finality {
+        Finality::Finalized
+    }
+}
+
+mod retry {
+    use {super::Retry, std::time::Duration};
+
+    pub fn default() -> Retry {
+        Retry {
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+            max_retries: None,
+        }
+    }
+
+    pub fn default_initial_backoff() -> Duration {
+        Duration::from_secs_f64(0.5)
+    }
+
+    pub fn default_max_backoff() -> Duration {
+        Duration::from_secs(60)
+    }
 }
 
 mod duration {
@@ -161,9 +626,208 @@ impl Event {
         Self {
             name: signature.name.clone(),
             start: 0,
+            end: None,
             contract: Contract::All,
             topics: ArrayVec::new(),
+            filter: BTreeMap::new(),
+            signature,
+            receipt: false,
+        }
+    }
+}
+
+impl Call {
+    #[cfg(test)]
+    pub fn for_signature(signature: &str) -> Self {
+        let signature = FunctionDescriptor::parse_declaration(signature).unwrap();
+        Self {
+            name: signature.name.clone(),
+            start: 0,
+            end: None,
+            contract: Contract::All,
             signature,
+            internal: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn networks() {
+        let config = toml::from_str::<Config>(
+            r#"
+            ethrpc = "http://localhost:8545"
+            database.sqlite.connection = ":memory:"
+
+            [[event]]
+            name = "transfers"
+            contract = "*"
+            signature = "event Transfer(address indexed from, address indexed to, uint256 value)"
+
+            [[network]]
+            name = "gnosis"
+            ethrpc = "http://localhost:8546"
+            indexer.finality = "safe"
+
+            [[network.event]]
+            name = "pools"
+            contract = "*"
+            signature = "event PoolCreated(address pool)"
+
+            [[network.event]]
+            name = "swaps"
+            contract = { from_event = "pools", field = "pool" }
+            signature = "event Swap()"
+
+            [[network.call]]
+            name = "settlements"
+            contract = "0x9008D19f58AAbD9eD0D60971565AA8510560ab41"
+            signature = "function settle(address[] tokens, uint256[] clearingPrices)"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let networks = config.networks();
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].events()[0].name, "transfers");
+        assert!(networks[0].indexer.is_none());
+
+        let events = networks[1].events();
+        assert_eq!(events[0].name, "gnosis_pools");
+        assert_eq!(events[1].name, "gnosis_swaps");
+        assert!(matches!(
+            &events[1].contract,
+            Contract::Factory(factory) if factory.from_event == "gnosis_pools"
+        ));
+        assert_eq!(networks[1].calls()[0].name, "gnosis_settlements");
+        assert_eq!(
+            networks[1].names(),
+            ["gnosis_pools", "gnosis_swaps", "gnosis_settlements"]
+        );
+        assert!(!networks[1].calls()[0].internal);
+        assert!(matches!(
+            networks[1].indexer,
+            Some(Indexer {
+                finality: Finality::Safe,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_table_names() {
+        let config = |network: &str| {
+            toml::from_str::<Config>(&format!(
+                r#"
+                ethrpc = "http://localhost:8545"
+                database.sqlite.connection = ":memory:"
+
+                [[event]]
+                name = "gnosis_pools"
+                contract = "*"
+                signature = "event PoolCreated(address pool)"
+
+                [[network]]
+                name = "{network}"
+                ethrpc = "http://localhost:8546"
+
+                [[network.event]]
+                name = "pools"
+                contract = "*"
+                signature = "event PoolCreated(address pool)"
+                "#
+            ))
+            .unwrap()
+        };
+        assert!(config("gnosis").validate().is_err());
+        assert!(config("Gnosis").validate().is_err());
+        config("xdai").validate().unwrap();
+
+        for network in ["", "_gnosis", "1gnosis", "gnosis-chain", "gnösis"] {
+            assert!(config(network).validate().is_err(), "{network}");
+        }
+    }
+
+    #[test]
+    fn transfers_require_traces() {
+        let mut config = toml::from_str::<Config>(
+            r#"
+            ethrpc = "http://localhost:8545"
+            database.sqlite.connection = ":memory:"
+
+            [[transfer]]
+            name = "settlement_transfers"
+            contract = "0x9008D19f58AAbD9eD0D60971565AA8510560ab41"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        config.indexer.traces = Some(Tracer::Parity);
+        config.validate().unwrap();
+        assert_eq!(
+            config.networks()[0].transfers()[0].name,
+            "settlement_transfers"
+        );
+    }
+
+    #[test]
+    fn end_blocks_follow_start() {
+        let mut config = toml::from_str::<Config>(
+            r#"
+            ethrpc = "http://localhost:8545"
+            database.sqlite.connection = ":memory:"
+
+            [[event]]
+            name = "transfers"
+            start = 100
+            end = 99
+            contract = "*"
+            signature = "event Transfer(address indexed from, address indexed to, uint256 value)"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        config.events[0].end = Some(100);
+        config.validate().unwrap();
+        assert_eq!(config.networks()[0].events()[0].end, Some(100));
+    }
+
+    #[test]
+    fn parses_snapshots() {
+        let config = toml::from_str::<Config>(
+            r#"
+            database.sqlite.connection = ":memory:"
+
+            [[network]]
+            name = "gnosis"
+            ethrpc = "http://localhost:8545"
+
+            [[network.snapshot]]
+            name = "balances"
+            start = 100
+            contract = ["0xe91D153E0b41518A2Ce8Dd3D7944Fa863463a97d"]
+            signature = "function balanceOf(address owner) returns (uint256)"
+            arguments = ["0x9008D19f58AAbD9eD0D60971565AA8510560ab41"]
+            interval = 7200
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let snapshots = config.networks()[0].snapshots();
+        assert_eq!(snapshots[0].name, "gnosis_balances");
+        assert_eq!(snapshots[0].interval, 7200);
+        assert_eq!(
+            snapshots[0].arguments,
+            [toml::Value::String(
+                "0x9008D19f58AAbD9eD0D60971565AA8510560ab41".to_string()
+            )]
+        );
+    }
+}
@@ -76,6 +76,32 @@ pub fn event_to_tables<'a>(name: &str, event: &'a EventDescriptor) -> Result<Tab
     })
 }
 
+/// Returns the name of the primary table column for the top-level event field
+/// at the specified input index. The field must be a single leaf value, like
+/// an address, that isn't part of a dynamic array.
+pub fn field_column(name: &str, event: &EventDescriptor, index: usize) -> Result<String> {
+    let input = event
+        .inputs
+        .get(index)
+        .ok_or_else(|| anyhow!("Event '{name}' has no field {index}."))?;
+    let mut primary = Table {
+        name: name.to_string(),
+        columns: Default::default(),
+    };
+    let mut dynamic_arrays = Vec::new();
+    for input in &event.inputs[..index] {
+        handle_field_simple_names(name, &mut primary, &mut dynamic_arrays, &input.field);
+    }
+    let columns = primary.columns.len();
+    handle_field_simple_names(name, &mut primary, &mut dynamic_arrays, &input.field);
+    if primary.columns.len() != columns + 1 {
+        return Err(anyhow!(
+            "Field {index} of event '{name}' isn't stored in a single column."
+        ));
+    }
+    Ok(primary.columns.pop().unwrap().name)
+}
+
 fn has_nested_dynamic_arrays(field: &Field) -> bool {
     let mut level: u32 = 0;
     let mut max_level: u32 = 0;
@@ -393,4 +419,17 @@ event Event(
         )];
         assert_tables(event, expected);
     }
+
+    #[test]
+    fn field_columns() {
+        let event = EventDescriptor::parse_declaration(
+            "event Event(uint256 a, (bool, uint8) b, address[] c, address d)",
+        )
+        .unwrap();
+        assert_eq!(field_column("event", &event, 0).unwrap(), "a_0");
+        assert_eq!(field_column("event", &event, 3).unwrap(), "d_3");
+        assert!(field_column("event", &event, 1).is_err());
+        assert!(field_column("event", &event, 2).is_err());
+        assert!(field_column("event", &event, 4).is_err());
+    }
 }
@@ -0,0 +1,311 @@
+//! Persisted event metadata
+//!
+//! Databases record how every event is stored: its canonical signature and the
+//! column layout of each of its tables as given by `event_to_tables`. This is
+//! checked when an event is prepared again, for example after a restart with a
+//! changed configuration, so that logs are never written into tables that were
+//! created for a different event.
+
+use {
+    crate::database::event_to_tables::Tables,
+    anyhow::{bail, Result},
+    solabi::{abi::EventDescriptor, ValueKind},
+    std::iter,
+};
+
+/// The names of the columns that every event table starts with. Array tables
+/// additionally have an `array_index` column after them. These match the
+/// `FIXED_COLUMNS` of the database backends.
+const FIXED_COLUMNS: [&str; 9] = [
+    "block_number",
+    "block_timestamp",
+    "block_hash",
+    "log_index",
+    "transaction_index",
+    "transaction_hash",
+    "event_ordinal",
+    "address",
+    "contract_label",
+];
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Metadata {
+    /// The canonical signature, like
+    /// `event Transfer(address indexed from, address indexed to, uint256 value)`.
+    pub signature: String,
+    /// The tables and their event columns, like
+    /// `transfer(from_0 address, to_1 address, value_2 uint256)`. Tables are
+    /// separated by `; `.
+    pub layout: String,
+}
+
+impl Metadata {
+    pub fn new(event: &EventDescriptor, tables: &Tables) -> Self {
+        let inputs = event
+            .inputs
+            .iter()
+            .map(|input| {
+                let mut declaration = kind_to_string(&input.field.kind);
+                if input.indexed {
+                    declaration.push_str(" indexed");
+                }
+                if !input.field.name.is_empty() {
+                    declaration.push(' ');
+                    declaration.push_str(&input.field.name);
+                }
+                declaration
+            })
+            .collect::<Vec<_>>();
+        let mut signature = format!("event {}({})", event.name, inputs.join(", "));
+        if event.anonymous {
+            signature.push_str(" anonymous");
+        }
+
+        let layout = iter::once(&tables.primary)
+            .chain(&tables.dynamic_arrays)
+            .map(|table| {
+                let columns = table
+                    .columns
+                    .iter()
+                    .map(|column| format!("{} {}", column.name, kind_to_string(column.kind)))
+                    .collect::<Vec<_>>();
+                format!("{}({})", table.name, columns.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        Self { signature, layout }
+    }
+
+    /// Checks that an event can be stored in tables that were created with the
+    /// `stored` metadata.
+    pub fn verify(&self, name: &str, stored: &Self) -> Result<()> {
+        if self.signature != stored.signature {
+            bail!(
+                "event {name} is stored in the database as `{}` but is configured as `{}`; \
+                 either configure the event under a new name, or drop its tables and its rows in \
+                 the internal tables to index it from scratch",
+                stored.signature,
+                self.signature
+            );
+        }
+        if self.layout != stored.layout {
+            bail!(
+                "event {name} is stored in the database in tables `{}` but would now be stored \
+                 in tables `{}`; this happens when the mapping of events to tables changed \
+                 between versions, drop its tables and its rows in the internal tables to index \
+                 it from scratch",
+                stored.layout,
+                self.layout
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks that an existing table starts with the fixed columns that are stored
+/// for every event. `columns` are the names and types of the table's columns
+/// in order, and are empty for tables that don't exist yet.
+///
+/// Tables are created with `CREATE TABLE IF NOT EXISTS`, so tables created by
+/// an older version with different fixed columns would be kept, and inserting
+/// into them would fail.
+pub fn verify_fixed_columns(
+    table: &str,
+    is_array: bool,
+    columns: &[(String, String)],
+) -> Result<()> {
+    if columns.is_empty() {
+        return Ok(());
+    }
+    let expected = FIXED_COLUMNS
+        .iter()
+        .copied()
+        .chain(is_array.then_some("array_index"))
+        .collect::<Vec<_>>();
+    let matches = columns.len() >= expected.len()
+        && iter::zip(columns, &expected)
+            .all(|((column, _), expected)| column.eq_ignore_ascii_case(expected));
+    if !matches {
+        bail!(
+            "table {table} has the columns `{}` but should start with `{}`; it was created by an \
+             older version, drop it along with its event's rows in the internal tables to index \
+             the event from scratch",
+            columns
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            expected.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks that an existing table for an event without recorded metadata has
+/// exactly the `expected` columns. `columns` are the names and types of the
+/// table's columns in order as reported by the database, and are empty for
+/// tables that don't exist yet.
+///
+/// Metadata is only recorded since it was introduced, so this is the only way
+/// to tell whether tables created before then match the configured event.
+pub fn verify_columns(
+    table: &str,
+    columns: &[(String, String)],
+    expected: &[(String, String)],
+) -> Result<()> {
+    if columns.is_empty() {
+        return Ok(());
+    }
+    let matches = columns.len() == expected.len()
+        && iter::zip(columns, expected).all(|((name, type_), (expected_name, expected_type))| {
+            name.eq_ignore_ascii_case(expected_name) && type_.eq_ignore_ascii_case(expected_type)
+        });
+    if !matches {
+        let layout = |columns: &[(String, String)]| {
+            columns
+                .iter()
+                .map(|(name, type_)| format!("{name} {type_}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        bail!(
+            "table {table} already exists with the columns `{}` but the event would be stored in \
+             the columns `{}`; either configure the event under a new name, or drop the table to \
+             index the event from scratch",
+            layout(columns),
+            layout(expected)
+        );
+    }
+    Ok(())
+}
+
+fn kind_to_string(kind: &ValueKind) -> String {
+    match kind {
+        ValueKind::Int(bits) => format!("int{bits}"),
+        ValueKind::Uint(bits) => format!("uint{bits}"),
+        ValueKind::Address => "address".to_string(),
+        ValueKind::Bool => "bool".to_string(),
+        ValueKind::FixedBytes(length) => format!("bytes{length}"),
+        ValueKind::Function => "function".to_string(),
+        ValueKind::Bytes => "bytes".to_string(),
+        ValueKind::String => "string".to_string(),
+        ValueKind::FixedArray(length, kind) => format!("{}[{length}]", kind_to_string(kind)),
+        ValueKind::Array(kind) => format!("{}[]", kind_to_string(kind)),
+        ValueKind::Tuple(kinds) => {
+            let kinds = kinds.iter().map(kind_to_string).collect::<Vec<_>>();
+            format!("({})", kinds.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::event_to_tables::event_to_tables;
+
+    fn metadata(name: &str, event: &str) -> Metadata {
+        let event = EventDescriptor::parse_declaration(event).unwrap();
+        let tables = event_to_tables(name, &event).unwrap();
+        Metadata::new(&event, &tables)
+    }
+
+    #[test]
+    fn canonical_signature_and_layout() {
+        let metadata = metadata(
+            "trade",
+            "event Trade(address indexed owner, (uint256 amount, bool sell) order, bytes4[2][] ids) anonymous",
+        );
+        assert_eq!(
+            metadata.signature,
+            "event Trade(address indexed owner, (uint256,bool) order, bytes4[2][] ids) anonymous"
+        );
+        assert_eq!(
+            metadata.layout,
+            "trade(owner_0 address, amount_1 uint256, sell_2 bool); trade_ids_0(ids_0 bytes4, ids_1 bytes4)"
+        );
+    }
+
+    #[test]
+    fn verify_outdated_fixed_columns() {
+        let columns = |columns: &[&str]| {
+            columns
+                .iter()
+                .map(|c| (c.to_string(), "INTEGER".to_string()))
+                .collect::<Vec<_>>()
+        };
+        let mut current = columns(&FIXED_COLUMNS);
+        current.push(("a_0".to_string(), "BLOB".to_string()));
+
+        assert!(verify_fixed_columns("event", false, &[]).is_ok());
+        assert!(verify_fixed_columns("event", false, &current).is_ok());
+        assert!(verify_fixed_columns("event", true, &current).is_err());
+        assert!(verify_fixed_columns(
+            "event",
+            false,
+            &columns(&[
+                "block_number",
+                "log_index",
+                "transaction_index",
+                "address",
+                "a_0"
+            ])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn verify_existing_columns() {
+        let columns = |columns: &[(&str, &str)]| {
+            columns
+                .iter()
+                .map(|(name, type_)| (name.to_string(), type_.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let expected = columns(&[("block_number", "INTEGER"), ("a_0", "BLOB")]);
+
+        assert!(verify_columns("event", &[], &expected).is_ok());
+        assert!(verify_columns("event", &expected, &expected).is_ok());
+        assert!(verify_columns(
+            "event",
+            &columns(&[("BLOCK_NUMBER", "integer"), ("A_0", "blob")]),
+            &expected
+        )
+        .is_ok());
+        assert!(verify_columns(
+            "event",
+            &columns(&[("block_number", "INTEGER"), ("a_0", "TEXT")]),
+            &expected
+        )
+        .is_err());
+        assert!(verify_columns(
+            "event",
+            &columns(&[("block_number", "INTEGER"), ("b_0", "BLOB")]),
+            &expected
+        )
+        .is_err());
+        assert!(
+            verify_columns("event", &columns(&[("block_number", "INTEGER")]), &expected).is_err()
+        );
+    }
+
+    #[test]
+    fn verify_mismatches() {
+        let stored = metadata("event", "event Event(uint256 a)");
+        assert!(metadata("event", "event Event(uint256 a)")
+            .verify("event", &stored)
+            .is_ok());
+        assert!(metadata("event", "event Event(uint256 indexed a)")
+            .verify("event", &stored)
+            .is_err());
+        assert!(metadata("event", "event Event(uint256 b)")
+            .verify("event", &stored)
+            .is_err());
+
+        let changed = Metadata {
+            layout: "event(a_0 int256)".to_string(),
+            ..metadata("event", "event Event(uint256 a)")
+        };
+        assert!(changed.verify("event", &stored).is_err());
+    }
+}
@@ -1,13 +1,18 @@
 mod event_to_tables;
 mod event_visitor;
 mod keywords;
+mod metadata;
 mod postgres;
 mod sqlite;
 
 use {
     anyhow::Result,
     futures::future::BoxFuture,
-    solabi::{abi::EventDescriptor, ethprim::Address, value::Value},
+    solabi::{
+        abi::EventDescriptor,
+        ethprim::{Address, Digest},
+        value::Value,
+    },
 };
 
 pub use self::{postgres::Postgres, sqlite::Sqlite};
@@ -19,6 +24,17 @@ pub struct Block {
     pub finalized: u64,
 }
 
+/// The filter that an event is indexed with, along with its start block. This
+/// is recorded in the internal `_event_filter` table so that configuration
+/// changes that don't match the indexed data can be detected.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Filter {
+    /// A description of the contracts and topics, or for calls and snapshots
+    /// their equivalents, that the event is indexed for.
+    pub fingerprint: String,
+    pub start: u64,
+}
+
 /// Block indexing information attached to an event.
 #[derive(Debug)]
 pub struct EventBlock<'a> {
@@ -38,12 +54,68 @@ pub struct Uncle<'a> {
 pub struct Log<'a> {
     pub event: &'a str,
     pub block_number: u64,
+    /// The block timestamp in seconds since the Unix epoch.
+    pub block_timestamp: u64,
+    pub block_hash: Digest,
     pub log_index: u64,
     pub transaction_index: u64,
+    pub transaction_hash: Digest,
+    /// The ordinal of the log among the rows of this event for the same
+    /// transaction, starting at 0. Logs that failed to decode are counted as
+    /// well. This is not the log's index within the transaction: it depends on
+    /// the event's filter and isn't comparable between events, so logs of
+    /// different events are ordered by `log_index` instead.
+    pub event_ordinal: u64,
     pub address: Address,
+    /// The label of the contract that emitted the log, if contracts are
+    /// configured with labels.
+    pub contract_label: Option<&'a str>,
     pub fields: Vec<Value>,
 }
 
+/// A log that could not be decoded with the signature of its event. These are
+/// stored in the internal `_undecoded_logs` table along with the raw log data,
+/// so that they are not lost.
+#[derive(Debug, Default)]
+pub struct UndecodedLog<'a> {
+    pub event: &'a str,
+    pub block_number: u64,
+    pub block_hash: Digest,
+    pub log_index: u64,
+    pub transaction_index: u64,
+    pub transaction_hash: Digest,
+    pub address: Address,
+    pub topics: Vec<Digest>,
+    pub data: Vec<u8>,
+    /// The error message of the decoding failure.
+    pub error: String,
+}
+
+/// The receipt of a transaction that emitted logs of an event with receipts
+/// enabled. These are stored in the internal `_transactions` table, keyed by
+/// network, block number and transaction index, which is shared by all events.
+/// Receipts are pruned when the blocks they are in are removed from their
+/// network and none of the remaining logs of any of its events were emitted by
+/// their transaction.
+#[derive(Debug, Default)]
+pub struct Receipt {
+    pub block_number: u64,
+    pub transaction_index: u64,
+    pub transaction_hash: Digest,
+    /// The sender, if the node reports it.
+    pub from: Option<Address>,
+    /// The recipient, or `None` for contract creations.
+    pub to: Option<Address>,
+    /// The gas used, if the node reports it.
+    pub gas_used: Option<u64>,
+    /// The effective gas price, if the node reports it. Nodes don't report it
+    /// for some blocks from before EIP-1559.
+    pub effective_gas_price: Option<u64>,
+    /// Whether the transaction succeeded, or `None` for transactions from
+    /// before the Byzantium fork, whose receipts have no status.
+    pub success: Option<bool>,
+}
+
 /// Abstraction over specific SQL like backends.
 ///
 /// All methods either succeed in full or error without having applied any
@@ -63,10 +135,14 @@ pub trait Database {
     /// then the event's indexed and finalized blocks (see `event_block`) are
     /// set to 0.
     ///
+    /// The event's canonical signature and table layout are recorded in the
+    /// internal `_event_metadata` table the first time it is prepared, and
+    /// verified against the recorded ones on subsequent calls.
+    ///
     /// Errors:
     ///
     /// - A table for `name` already exists with an incompatible event
-    ///   signature.
+    ///   signature or table layout.
     fn prepare_event<'a>(
         &'a mut self,
         name: &'a str,
@@ -76,27 +152,119 @@ pub trait Database {
     /// Retrieves the block information for the specified event.
     fn event_block<'a>(&'a mut self, name: &'a str) -> BoxFuture<'a, Result<Block>>;
 
-    /// It updates two things:
+    /// Retrieves the distinct addresses stored in an address field of the
+    /// specified event, along with the first block each address appears in.
+    ///
+    /// `field` is the index of a top-level address field of the event. This is
+    /// used for discovering contracts from factory events.
+    fn event_addresses<'a>(
+        &'a mut self,
+        name: &'a str,
+        field: usize,
+    ) -> BoxFuture<'a, Result<Vec<(Address, u64)>>>;
+
+    /// Retrieves the filter that the specified event was last recorded with,
+    /// or `None` if it was never recorded.
+    fn event_filter<'a>(&'a mut self, name: &'a str) -> BoxFuture<'a, Result<Option<Filter>>>;
+
+    /// Records the filter of the specified event, replacing any previously
+    /// recorded one.
+    ///
+    /// Errors:
+    ///
+    /// - `prepare_event` has not been successfully called with `name`.
+    fn set_event_filter<'a>(
+        &'a mut self,
+        name: &'a str,
+        filter: &'a Filter,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// It updates four things:
     /// - `blocks` specifies updates to the block information for events; this
     ///   will change the value that is read from `event_block`.
     /// - `logs` specified new logs to append to the database.
+    /// - `undecoded` specifies new logs that failed to decode to append to the
+    ///   `_undecoded_logs` table.
+    /// - `receipts` specifies new transaction receipts to append to the
+    ///   `_transactions` table.
     ///
     /// Errors:
     ///
     /// - `prepare_event` has not been successfully called with `event` field
-    ///   from one or more of the specified `blocks` or `logs`.
+    ///   from one or more of the specified `blocks`, `logs` or `receipts`.
     /// - `fields` do not match the event signature specified in the successful
     ///   call to `prepare_event` with this `event` name for one or more `logs`.
     fn update<'a>(
         &'a mut self,
         blocks: &'a [EventBlock],
         logs: &'a [Log],
+        undecoded: &'a [UndecodedLog],
+        receipts: &'a [Receipt],
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Stores logs for blocks before the ones that were already indexed for
+    /// the specified event and records its filter, in a single transaction.
+    /// Unlike `update`, this doesn't change the indexed block of the event.
+    ///
+    /// This is used to index the blocks that an event newly covers after its
+    /// start block moved earlier, in which case the recorded start block is
+    /// the earliest block that was stored so far.
+    ///
+    /// Errors:
+    ///
+    /// - `prepare_event` has not been successfully called with `name` or the
+    ///   `event` field of one or more of the specified `logs`.
+    fn prepend<'a>(
+        &'a mut self,
+        name: &'a str,
+        filter: &'a Filter,
+        logs: &'a [Log],
+        undecoded: &'a [UndecodedLog],
+        receipts: &'a [Receipt],
     ) -> BoxFuture<'a, Result<()>>;
 
-    /// Removes logs from the specified event's uncled blocks.
+    /// Removes logs, including undecoded logs, from the specified event's
+    /// uncled blocks. Transaction receipts from the earliest uncled block on
+    /// are removed as well, unless their transaction still has logs of an
+    /// event.
     ///
     /// Additionally the last indexed block is set to the uncled block's parent;
     /// this changes the `indexed` field of the result from `event_block` for
     /// the specified events.
     fn remove<'a>(&'a mut self, uncles: &'a [Uncle]) -> BoxFuture<'a, Result<()>>;
+
+    /// Removes logs from the specified event's uncled blocks and then applies
+    /// the specified updates, in a single transaction.
+    ///
+    /// This has the same effect as calling `remove` followed by `update`,
+    /// except that the state with the uncled blocks removed but the new blocks
+    /// not yet applied is never observable.
+    fn replace<'a>(
+        &'a mut self,
+        uncles: &'a [Uncle],
+        blocks: &'a [EventBlock],
+        logs: &'a [Log],
+        undecoded: &'a [UndecodedLog],
+        receipts: &'a [Receipt],
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Returns the SQL statement that removes the receipts of the specified network
+/// parameter from the specified block parameter on, except for those of
+/// transactions that still have logs in the primary table of any of the
+/// specified events of the network. Receipts are shared between events, so
+/// removing the blocks of a single event, for example when reindexing it, must
+/// keep the receipts that other events need.
+fn remove_transactions_sql(events: &[&String], network: &str, block: &str) -> String {
+    let mut sql =
+        format!("DELETE FROM _transactions WHERE network = {network} AND block_number >= {block}");
+    for event in events {
+        sql.push_str(&format!(
+            " AND NOT EXISTS (SELECT 1 FROM {event} WHERE {event}.block_number = \
+             _transactions.block_number AND {event}.transaction_index = \
+             _transactions.transaction_index)"
+        ));
+    }
+    sql.push(';');
+    sql
 }
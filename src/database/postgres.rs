@@ -3,6 +3,7 @@ use {
         self,
         event_to_tables::Table,
         event_visitor::{self, VisitValue},
+        metadata::{self, Metadata},
         Database, Log,
     },
     anyhow::{anyhow, Context, Result},
@@ -10,6 +11,7 @@ use {
     pg_bigdecimal::{BigDecimal, PgNumeric},
     solabi::{
         abi::EventDescriptor,
+        ethprim::Address,
         value::{Value as AbiValue, ValueKind as AbiKind},
     },
     std::{collections::HashMap, fmt::Write, str::FromStr},
@@ -22,11 +24,20 @@ pub struct Postgres {
     /// The key is the `name` argument when the event was passed into
     /// `prepare_event`.
     events: HashMap<String, PreparedEvent>,
+    /// The network that receipts are stored for.
+    network: String,
 
     get_event_block: tokio_postgres::Statement,
     set_event_block: tokio_postgres::Statement,
     set_indexed_block: tokio_postgres::Statement,
     new_event_block: tokio_postgres::Statement,
+    insert_undecoded_log: tokio_postgres::Statement,
+    remove_undecoded_logs: tokio_postgres::Statement,
+    insert_transaction: tokio_postgres::Statement,
+    get_event_metadata: tokio_postgres::Statement,
+    new_event_metadata: tokio_postgres::Statement,
+    get_event_filter: tokio_postgres::Statement,
+    set_event_filter: tokio_postgres::Statement,
 }
 
 /// An event is represented in the database in several tables.
@@ -61,7 +72,9 @@ async fn connect(params: &str) -> Result<tokio_postgres::Client> {
 }
 
 impl Postgres {
-    pub async fn connect(params: &str) -> Result<Self> {
+    /// Connects to the database for the events of the specified network. Its
+    /// name is empty for the unnamed network.
+    pub async fn connect(params: &str, network: &str) -> Result<Self> {
         tracing::debug!("opening postgres database");
         let client = connect(params).await.context("connect")?;
 
@@ -69,6 +82,22 @@ impl Postgres {
             .execute(CREATE_EVENT_BLOCK_TABLE, &[])
             .await
             .context("create event_block table")?;
+        client
+            .execute(CREATE_UNDECODED_LOGS_TABLE, &[])
+            .await
+            .context("create undecoded_logs table")?;
+        client
+            .execute(CREATE_TRANSACTIONS_TABLE, &[])
+            .await
+            .context("create transactions table")?;
+        client
+            .execute(CREATE_EVENT_METADATA_TABLE, &[])
+            .await
+            .context("create event_metadata table")?;
+        client
+            .execute(CREATE_EVENT_FILTER_TABLE, &[])
+            .await
+            .context("create event_filter table")?;
 
         let get_event_block = client
             .prepare(GET_EVENT_BLOCK)
@@ -86,14 +115,50 @@ impl Postgres {
             .prepare(NEW_EVENT_BLOCK)
             .await
             .context("prepare new_event_block")?;
+        let insert_undecoded_log = client
+            .prepare(INSERT_UNDECODED_LOG)
+            .await
+            .context("prepare INSERT_UNDECODED_LOG")?;
+        let remove_undecoded_logs = client
+            .prepare(REMOVE_UNDECODED_LOGS)
+            .await
+            .context("prepare REMOVE_UNDECODED_LOGS")?;
+        let insert_transaction = client
+            .prepare(INSERT_TRANSACTION)
+            .await
+            .context("prepare INSERT_TRANSACTION")?;
+        let get_event_metadata = client
+            .prepare(GET_EVENT_METADATA)
+            .await
+            .context("prepare GET_EVENT_METADATA")?;
+        let new_event_metadata = client
+            .prepare(NEW_EVENT_METADATA)
+            .await
+            .context("prepare NEW_EVENT_METADATA")?;
+        let get_event_filter = client
+            .prepare(GET_EVENT_FILTER)
+            .await
+            .context("prepare GET_EVENT_FILTER")?;
+        let set_event_filter = client
+            .prepare(SET_EVENT_FILTER)
+            .await
+            .context("prepare SET_EVENT_FILTER")?;
 
         Ok(Self {
             client,
             events: Default::default(),
+            network: network.to_owned(),
             get_event_block,
             set_event_block,
             set_indexed_block,
             new_event_block,
+            insert_undecoded_log,
+            remove_undecoded_logs,
+            insert_transaction,
+            get_event_metadata,
+            new_event_metadata,
+            get_event_filter,
+            set_event_filter,
         })
     }
 }
@@ -107,12 +172,8 @@ impl Database for Postgres {
         async move {
             let transaction = self.client.transaction().await.context("transaction")?;
             // TODO:
-            // - Check that either no table exists or all tables exist and with the right
-            //   types.
             // - Maybe have `CHECK` clauses to enforce things like address and integers
             //   having expected length.
-            // - Maybe store serialized event descriptor in the database so we can load and
-            //   check it.
 
             if let Some(existing) = self.events.get(name) {
                 if event != &existing.descriptor {
@@ -127,6 +188,47 @@ impl Database for Postgres {
             let tables = database::event_to_tables::event_to_tables(name, event)
                 .context("unsupported event")?;
             let name = &tables.primary.name;
+
+            let mut existing = Vec::new();
+            for (is_array, table) in std::iter::once((false, &tables.primary))
+                .chain(std::iter::repeat(true).zip(&tables.dynamic_arrays))
+            {
+                let columns = Self::table_columns(&transaction, &table.name).await?;
+                metadata::verify_fixed_columns(&table.name, is_array, &columns)?;
+                existing.push((is_array, table, columns));
+            }
+
+            let metadata = Metadata::new(event, &tables);
+            let stored = transaction
+                .query_opt(&self.get_event_metadata, &[name])
+                .await
+                .context("query GET_EVENT_METADATA")?;
+            match stored {
+                Some(row) => {
+                    let stored = Metadata {
+                        signature: row.try_get(0)?,
+                        layout: row.try_get(1)?,
+                    };
+                    metadata.verify(name, &stored)?;
+                }
+                None => {
+                    for (is_array, table, columns) in &existing {
+                        metadata::verify_columns(
+                            &table.name,
+                            columns,
+                            &expected_columns(*is_array, table),
+                        )?;
+                    }
+                    transaction
+                        .execute(
+                            &self.new_event_metadata,
+                            &[name, &metadata.signature, &metadata.layout],
+                        )
+                        .await
+                        .context("execute NEW_EVENT_METADATA")?;
+                }
+            }
+
             Self::create_table(&transaction, false, &tables.primary).await?;
             for table in &tables.dynamic_arrays {
                 Self::create_table(&transaction, true, table).await?;
@@ -199,14 +301,168 @@ impl Database for Postgres {
         .boxed()
     }
 
+    fn event_filter<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<database::Filter>>> {
+        async move {
+            let row = self
+                .client
+                .query_opt(&self.get_event_filter, &[&name])
+                .await
+                .context("query GET_EVENT_FILTER")?;
+            row.map(|row| -> Result<database::Filter> {
+                let start: i64 = row.try_get(1)?;
+                Ok(database::Filter {
+                    fingerprint: row.try_get(0)?,
+                    start: start.try_into().context("start out of bounds")?,
+                })
+            })
+            .transpose()
+        }
+        .boxed()
+    }
+
+    fn set_event_filter<'a>(
+        &'a mut self,
+        name: &'a str,
+        filter: &'a database::Filter,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            if !self.events.contains_key(name) {
+                return Err(anyhow!("event {name} wasn't prepared"));
+            }
+            let start: i64 = filter.start.try_into().context("start out of bounds")?;
+            self.client
+                .execute(
+                    &self.set_event_filter,
+                    &[&name, &filter.fingerprint, &start],
+                )
+                .await
+                .context("execute SET_EVENT_FILTER")?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn event_addresses<'a>(
+        &'a mut self,
+        name: &'a str,
+        field: usize,
+    ) -> BoxFuture<'a, Result<Vec<(Address, u64)>>> {
+        async move {
+            let prepared = self.events.get(name).context("unprepared event")?;
+            let column =
+                database::event_to_tables::field_column(name, &prepared.descriptor, field)?;
+            let rows = self
+                .client
+                .query(
+                    &format!("SELECT {column}, MIN(block_number) FROM {name} GROUP BY {column};"),
+                    &[],
+                )
+                .await
+                .context("query event_addresses")?;
+            rows.into_iter()
+                .map(|row| {
+                    let address: Vec<u8> = row.try_get(0)?;
+                    let block: i64 = row.try_get(1)?;
+                    Ok((
+                        Address(address.try_into().map_err(|_| anyhow!("invalid address"))?),
+                        block.try_into().context("block out of bounds")?,
+                    ))
+                })
+                .collect()
+        }
+        .boxed()
+    }
+
     fn update<'a>(
         &'a mut self,
         blocks: &'a [database::EventBlock],
         logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
+    ) -> BoxFuture<'a, Result<()>> {
+        self.replace(&[], blocks, logs, undecoded, receipts)
+    }
+
+    fn prepend<'a>(
+        &'a mut self,
+        name: &'a str,
+        filter: &'a database::Filter,
+        logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
+    ) -> BoxFuture<'a, Result<()>> {
+        self.write(&[], &[], logs, undecoded, receipts, Some((name, filter)))
+    }
+
+    fn remove<'a>(&'a mut self, uncles: &'a [database::Uncle]) -> BoxFuture<'a, Result<()>> {
+        self.replace(uncles, &[], &[], &[], &[])
+    }
+
+    fn replace<'a>(
+        &'a mut self,
+        uncles: &'a [database::Uncle],
+        blocks: &'a [database::EventBlock],
+        logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
+    ) -> BoxFuture<'a, Result<()>> {
+        self.write(uncles, blocks, logs, undecoded, receipts, None)
+    }
+}
+
+impl Postgres {
+    /// Applies the changes of `replace`, and records the filter of an event if
+    /// specified, in a single transaction.
+    fn write<'a>(
+        &'a mut self,
+        uncles: &'a [database::Uncle],
+        blocks: &'a [database::EventBlock],
+        logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
+        filter: Option<(&'a str, &'a database::Filter)>,
     ) -> BoxFuture<'a, Result<()>> {
         async move {
             let mut transaction = self.client.transaction().await.context("transaction")?;
 
+            for uncle in uncles {
+                if uncle.number == 0 {
+                    return Err(anyhow!("block 0 got uncled"));
+                }
+                let block = i64::try_from(uncle.number).context("block out of bounds")?;
+                let parent_block = block - 1;
+                let prepared = self.events.get(uncle.event).context("unprepared event")?;
+                for remove_statement in &prepared.remove_statements {
+                    transaction
+                        .execute(remove_statement, &[&block])
+                        .await
+                        .context("execute remove_statement")?;
+                    transaction
+                        .execute(&self.set_indexed_block, &[&uncle.event, &parent_block])
+                        .await
+                        .context("execute set_indexed_block")?;
+                }
+                transaction
+                    .execute(&self.remove_undecoded_logs, &[&uncle.event, &block])
+                    .await
+                    .context("execute remove_undecoded_logs")?;
+            }
+            if let Some(block) = uncles.iter().map(|uncle| uncle.number).min() {
+                let block = i64::try_from(block).context("block out of bounds")?;
+                let mut events = self.events.keys().collect::<Vec<_>>();
+                events.sort();
+                transaction
+                    .execute(
+                        &database::remove_transactions_sql(&events, "$1", "$2"),
+                        &[&self.network, &block],
+                    )
+                    .await
+                    .context("execute remove_transactions")?;
+            }
+
             for block in blocks {
                 if !self.events.contains_key(block.event) {
                     return Err(anyhow!("event {} wasn't prepared", block.event));
@@ -238,50 +494,98 @@ impl Database for Postgres {
                     .context("store_event")?;
             }
 
-            transaction.commit().await.context("commit")
-        }
-        .boxed()
-    }
+            for log in undecoded {
+                if !self.events.contains_key(log.event) {
+                    return Err(anyhow!("event {} wasn't prepared", log.event));
+                }
+                let topics = log
+                    .topics
+                    .iter()
+                    .flat_map(|topic| topic.0)
+                    .collect::<Vec<_>>();
+                transaction
+                    .execute(
+                        &self.insert_undecoded_log,
+                        &[
+                            &log.event,
+                            &i64::try_from(log.block_number).context("block out of bounds")?,
+                            &log.block_hash.0.as_slice(),
+                            &i64::try_from(log.log_index).context("log index out of bounds")?,
+                            &i64::try_from(log.transaction_index)
+                                .context("transaction index out of bounds")?,
+                            &log.transaction_hash.0.as_slice(),
+                            &log.address.0.as_slice(),
+                            &topics,
+                            &log.data,
+                            &log.error,
+                        ],
+                    )
+                    .await
+                    .context("execute insert_undecoded_log")?;
+            }
 
-    fn remove<'a>(&'a mut self, uncles: &'a [database::Uncle]) -> BoxFuture<'a, Result<()>> {
-        async move {
-            let transaction = self.client.transaction().await.context("transaction")?;
+            for receipt in receipts {
+                transaction
+                    .execute(
+                        &self.insert_transaction,
+                        &[
+                            &self.network,
+                            &i64::try_from(receipt.block_number).context("block out of bounds")?,
+                            &i64::try_from(receipt.transaction_index)
+                                .context("transaction index out of bounds")?,
+                            &receipt.transaction_hash.0.as_slice(),
+                            &receipt.from.as_ref().map(|from| from.0.as_slice()),
+                            &receipt.to.as_ref().map(|to| to.0.as_slice()),
+                            &receipt
+                                .gas_used
+                                .map(i64::try_from)
+                                .transpose()
+                                .context("gas used out of bounds")?,
+                            &receipt
+                                .effective_gas_price
+                                .map(i64::try_from)
+                                .transpose()
+                                .context("gas price out of bounds")?,
+                            &receipt.success,
+                        ],
+                    )
+                    .await
+                    .context("execute insert_transaction")?;
+            }
 
-            for uncle in uncles {
-                if uncle.number == 0 {
-                    return Err(anyhow!("block 0 got uncled"));
-                }
-                let block = i64::try_from(uncle.number).context("block out of bounds")?;
-                let parent_block = block - 1;
-                let prepared = self.events.get(uncle.event).context("unprepared event")?;
-                for remove_statement in &prepared.remove_statements {
-                    transaction
-                        .execute(remove_statement, &[&block])
-                        .await
-                        .context("execute remove_statement")?;
-                    transaction
-                        .execute(&self.set_indexed_block, &[&uncle.event, &parent_block])
-                        .await
-                        .context("execute set_indexed_block")?;
+            if let Some((name, filter)) = filter {
+                if !self.events.contains_key(name) {
+                    return Err(anyhow!("event {name} wasn't prepared"));
                 }
+                let start: i64 = filter.start.try_into().context("start out of bounds")?;
+                transaction
+                    .execute(
+                        &self.set_event_filter,
+                        &[&name, &filter.fingerprint, &start],
+                    )
+                    .await
+                    .context("execute SET_EVENT_FILTER")?;
             }
 
             transaction.commit().await.context("commit")
         }
         .boxed()
     }
-}
 
-impl Postgres {
     async fn store_event<'a>(
         transaction: &mut tokio_postgres::Transaction<'a>,
         events: &HashMap<String, PreparedEvent>,
         Log {
             event,
             block_number,
+            block_timestamp,
+            block_hash,
             log_index,
             transaction_index,
+            transaction_hash,
+            event_ordinal,
             address,
+            contract_label,
             fields,
         }: &'a Log<'a>,
     ) -> Result<()> {
@@ -352,8 +656,12 @@ impl Postgres {
         }
 
         let block_number = i64::try_from(*block_number).unwrap();
+        let block_timestamp = i64::try_from(*block_timestamp).unwrap();
+        let block_hash = block_hash.0.as_slice();
         let log_index = i64::try_from(*log_index).unwrap();
         let transaction_index = i64::try_from(*transaction_index).unwrap();
+        let transaction_hash = transaction_hash.0.as_slice();
+        let event_ordinal = i64::try_from(*event_ordinal).unwrap();
         let address = address.0.as_slice();
         for (statement, (array_element_count, values)) in
             event.insert_statements.iter().zip(sql_values)
@@ -370,9 +678,14 @@ impl Postgres {
                 };
                 let params: Vec<_> = [
                     &block_number as &(dyn tokio_postgres::types::ToSql + Sync),
+                    &block_timestamp,
+                    &block_hash,
                     &log_index,
                     &transaction_index,
+                    &transaction_hash,
+                    &event_ordinal,
                     &address,
+                    contract_label,
                 ]
                 .into_iter()
                 .chain(
@@ -395,6 +708,22 @@ impl Postgres {
         Ok(())
     }
 
+    /// Returns the names and data types of the columns of a table in order,
+    /// or an empty list if the table doesn't exist.
+    async fn table_columns(
+        transaction: &tokio_postgres::Transaction<'_>,
+        table: &str,
+    ) -> Result<Vec<(String, String)>> {
+        // Unquoted identifiers are folded to lower case.
+        let rows = transaction
+            .query(TABLE_COLUMNS, &[&table.to_lowercase()])
+            .await
+            .context("query TABLE_COLUMNS")?;
+        rows.iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect()
+    }
+
     async fn create_table<'a>(
         transaction: &tokio_postgres::Transaction<'a>,
         is_array: bool,
@@ -431,9 +760,12 @@ impl Postgres {
 }
 
 /// Columns that every event table has.
-const FIXED_COLUMNS: &str = "block_number BIGINT NOT NULL, log_index BIGINT NOT NULL, \
-                             transaction_index BIGINT NOT NULL, address BYTEA NOT NULL";
-const FIXED_COLUMNS_COUNT: usize = 4;
+const FIXED_COLUMNS: &str = "block_number BIGINT NOT NULL, block_timestamp BIGINT NOT NULL, \
+                             block_hash BYTEA NOT NULL, log_index BIGINT NOT NULL, \
+                             transaction_index BIGINT NOT NULL, transaction_hash BYTEA NOT NULL, \
+                             event_ordinal BIGINT NOT NULL, address BYTEA NOT NULL, \
+                             contract_label TEXT";
+const FIXED_COLUMNS_COUNT: usize = 9;
 const PRIMARY_KEY: &str = "block_number, log_index";
 
 /// Column for array tables.
@@ -450,11 +782,66 @@ const SET_EVENT_BLOCK: &str =
     "UPDATE _event_block SET indexed = $2, finalized = $3 WHERE event = $1;";
 const SET_INDEXED_BLOCK: &str = "UPDATE _event_block SET indexed = $2 WHERE event = $1";
 
+const CREATE_UNDECODED_LOGS_TABLE: &str = "CREATE TABLE IF NOT EXISTS _undecoded_logs(event \
+                                           TEXT NOT NULL, block_number BIGINT NOT NULL, \
+                                           block_hash BYTEA NOT NULL, log_index BIGINT NOT NULL, \
+                                           transaction_index BIGINT NOT NULL, transaction_hash \
+                                           BYTEA NOT NULL, address BYTEA NOT NULL, topics BYTEA \
+                                           NOT NULL, data BYTEA NOT NULL, error TEXT NOT NULL, \
+                                           PRIMARY KEY(event, block_number, log_index));";
+const INSERT_UNDECODED_LOG: &str = "INSERT INTO _undecoded_logs (event, block_number, block_hash, \
+                                    log_index, transaction_index, transaction_hash, address, \
+                                    topics, data, error) VALUES($1, $2, $3, $4, $5, $6, $7, $8, \
+                                    $9, $10);";
+const REMOVE_UNDECODED_LOGS: &str =
+    "DELETE FROM _undecoded_logs WHERE event = $1 AND block_number >= $2;";
+
+const CREATE_TRANSACTIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS \
+                                         _transactions(network TEXT NOT NULL, block_number \
+                                         BIGINT NOT NULL, transaction_index BIGINT NOT NULL, \
+                                         transaction_hash BYTEA NOT NULL, from_ BYTEA, to_ BYTEA, \
+                                         gas_used BIGINT, effective_gas_price BIGINT, status \
+                                         BOOLEAN, PRIMARY KEY(network, block_number, \
+                                         transaction_index));";
+/// Transactions with logs of multiple events are stored once.
+const INSERT_TRANSACTION: &str = "INSERT INTO _transactions (network, block_number, \
+                                  transaction_index, transaction_hash, from_, to_, gas_used, \
+                                  effective_gas_price, status) VALUES($1, $2, $3, $4, $5, $6, $7, \
+                                  $8, $9) ON CONFLICT(network, block_number, transaction_index) \
+                                  DO NOTHING;";
+
+const CREATE_EVENT_METADATA_TABLE: &str = "CREATE TABLE IF NOT EXISTS _event_metadata(event \
+                                            TEXT PRIMARY KEY NOT NULL, signature TEXT NOT NULL, \
+                                            layout TEXT NOT NULL);";
+const GET_EVENT_METADATA: &str = "SELECT signature, layout FROM _event_metadata WHERE event = $1;";
+const NEW_EVENT_METADATA: &str =
+    "INSERT INTO _event_metadata (event, signature, layout) VALUES($1, $2, $3);";
+
+const CREATE_EVENT_FILTER_TABLE: &str = "CREATE TABLE IF NOT EXISTS _event_filter(event TEXT \
+                                         PRIMARY KEY NOT NULL, fingerprint TEXT NOT NULL, start \
+                                         BIGINT NOT NULL);";
+const GET_EVENT_FILTER: &str = "SELECT fingerprint, start FROM _event_filter WHERE event = $1;";
+const SET_EVENT_FILTER: &str = "INSERT INTO _event_filter (event, fingerprint, start) VALUES($1, \
+                                $2, $3) ON CONFLICT(event) DO UPDATE SET fingerprint = \
+                                excluded.fingerprint, start = excluded.start;";
+
+const TABLE_COLUMNS: &str = "SELECT column_name::TEXT, data_type::TEXT FROM \
+                             information_schema.columns WHERE \
+                             table_schema = current_schema() AND table_name = $1 ORDER BY \
+                             ordinal_position;";
+
 /// Parameters:
 /// - 1: block number
-/// - 2: log index
-/// - 3: array index if this is an array table (all tables after the first)
-/// - 3 + n: n-th event field/column
+/// - 2: block timestamp
+/// - 3: block hash
+/// - 4: log index
+/// - 5: transaction index
+/// - 6: transaction hash
+/// - 7: transaction event index
+/// - 8: address
+/// - 9: contract label
+/// - 10: array index if this is an array table (all tables after the first)
+/// - 10 + n: n-th event field/column
 struct InsertStatement {
     sql: tokio_postgres::Statement,
     /// Number of event fields that map to SQL columns. Does not count
@@ -470,6 +857,32 @@ impl std::fmt::Debug for InsertStatement {
     }
 }
 
+/// Returns the names and data types of the columns that an event table is
+/// created with. The types are spelled like `information_schema.columns`
+/// reports them, up to case.
+fn expected_columns(is_array: bool, table: &Table) -> Vec<(String, String)> {
+    FIXED_COLUMNS
+        .split(", ")
+        .chain(is_array.then_some(ARRAY_COLUMN))
+        .map(|column| {
+            let mut words = column.split_whitespace();
+            let name = words.next().expect("column name");
+            let type_ = words.next().expect("column type");
+            (name.to_string(), type_.to_string())
+        })
+        .chain(table.columns.iter().map(|column| {
+            let type_ = match abi_kind_to_sql_type(column.kind).unwrap() {
+                tokio_postgres::types::Type::NUMERIC => "NUMERIC",
+                tokio_postgres::types::Type::BYTEA => "BYTEA",
+                tokio_postgres::types::Type::BOOL => "BOOLEAN",
+                tokio_postgres::types::Type::TEXT => "TEXT",
+                _ => unreachable!(),
+            };
+            (column.name.clone(), type_.to_string())
+        }))
+        .collect()
+}
+
 fn abi_kind_to_sql_type(value: &AbiKind) -> Option<tokio_postgres::types::Type> {
     match value {
         AbiKind::Int(_) => Some(tokio_postgres::types::Type::NUMERIC),
@@ -516,7 +929,7 @@ END $$;
     #[tokio::test]
     async fn large_number() {
         clear_database().await;
-        let mut db = Postgres::connect(&local_postgres_url()).await.unwrap();
+        let mut db = Postgres::connect(&local_postgres_url(), "").await.unwrap();
         let event = r#"
 event Event (
     uint256,
@@ -534,6 +947,6 @@ event Event (
             ],
             ..Default::default()
         };
-        db.update(&[], &[log]).await.unwrap();
+        db.update(&[], &[log], &[], &[]).await.unwrap();
     }
 }
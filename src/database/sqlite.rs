@@ -3,19 +3,21 @@ use {
         self,
         event_to_tables::Table,
         event_visitor::{self, VisitValue},
+        metadata::{self, Metadata},
         Database, Log,
     },
     anyhow::{anyhow, Context, Result},
     futures::{future::BoxFuture, FutureExt},
     rusqlite::{
         types::{ToSqlOutput, Type as SqlType, Value as SqlValue, ValueRef as SqlValueRef},
-        Connection, Transaction,
+        Connection, OptionalExtension, Transaction, TransactionBehavior,
     },
     solabi::{
         abi::EventDescriptor,
+        ethprim::Address,
         value::{Value as AbiValue, ValueKind as AbiKind},
     },
-    std::{collections::HashMap, fmt::Write},
+    std::{collections::HashMap, fmt::Write, time::Duration},
 };
 
 pub struct Sqlite {
@@ -24,23 +26,73 @@ pub struct Sqlite {
 }
 
 impl Sqlite {
-    pub fn new(connection: Connection) -> Result<Self> {
-        let inner = SqliteInner::new(&connection)?;
+    /// Creates a database backend for the events of the specified network. Its
+    /// name is empty for the unnamed network.
+    pub fn new(connection: Connection, network: &str) -> Result<Self> {
+        let inner = SqliteInner::new(&connection, network)?;
         Ok(Self { connection, inner })
     }
 
-    /// Opens a new SQLite database backend for the specified connection string.
-    /// The connection string can either be a file path or a `file://` URL (see
-    /// <https://www.sqlite.org/uri.html> for more information).
-    pub fn open(connection: &str) -> Result<Self> {
+    /// Opens a new SQLite database backend for the specified connection string
+    /// and network. The connection string can either be a file path or a
+    /// `file://` URL (see <https://www.sqlite.org/uri.html> for more
+    /// information).
+    ///
+    /// Multiple networks index into the same database file with a connection
+    /// each, so the database uses write-ahead logging, which lets readers
+    /// continue while another connection writes, and writers wait for each
+    /// other instead of failing with `SQLITE_BUSY`.
+    pub fn open(connection: &str, network: &str) -> Result<Self> {
         let connection = Connection::open(connection)?;
-        Self::new(connection)
+        connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .context("set busy timeout")?;
+        connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .context("enable write-ahead logging")?;
+        Self::new(connection, network)
     }
 
     #[cfg(test)]
     /// Create a temporary in memory database for tests.
     pub fn new_for_test() -> Self {
-        Self::new(Connection::open_in_memory().unwrap()).unwrap()
+        Self::new(Connection::open_in_memory().unwrap(), "").unwrap()
+    }
+
+    #[cfg(test)]
+    /// Reads the block numbers of all rows in a table in order.
+    pub fn block_numbers(&self, table: &str) -> Vec<u64> {
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT block_number FROM {table} ORDER BY block_number, log_index"
+            ))
+            .unwrap();
+        let rows = statement
+            .query_map((), |row| row.get::<_, i64>(0))
+            .unwrap()
+            .map(|block| block.unwrap() as u64)
+            .collect();
+        rows
+    }
+
+    #[cfg(test)]
+    /// Returns the block numbers and transaction indices of the stored
+    /// receipts.
+    pub fn transactions(&self) -> Vec<(u64, u64)> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT block_number, transaction_index FROM _transactions ORDER BY block_number, \
+                 transaction_index",
+            )
+            .unwrap();
+        let rows = statement
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        rows
     }
 }
 
@@ -51,7 +103,10 @@ impl Database for Sqlite {
         event: &'a EventDescriptor,
     ) -> BoxFuture<'a, Result<()>> {
         async move {
-            let transaction = self.connection.transaction().context("transaction")?;
+            let transaction = self
+                .connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .context("transaction")?;
             self.inner.prepare_event(&transaction, name, event)?;
             transaction.commit().context("commit")
         }
@@ -62,14 +117,64 @@ impl Database for Sqlite {
         async move { self.inner.event_block(&self.connection, name) }.boxed()
     }
 
+    fn event_addresses<'a>(
+        &'a mut self,
+        name: &'a str,
+        field: usize,
+    ) -> BoxFuture<'a, Result<Vec<(Address, u64)>>> {
+        async move { self.inner.event_addresses(&self.connection, name, field) }.boxed()
+    }
+
+    fn event_filter<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<database::Filter>>> {
+        async move { self.inner.event_filter(&self.connection, name) }.boxed()
+    }
+
+    fn set_event_filter<'a>(
+        &'a mut self,
+        name: &'a str,
+        filter: &'a database::Filter,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move { self.inner.set_event_filter(&self.connection, name, filter) }.boxed()
+    }
+
     fn update<'a>(
         &'a mut self,
         blocks: &'a [database::EventBlock],
         logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let transaction = self
+                .connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .context("transaction")?;
+            self.inner
+                .update(&transaction, blocks, logs, undecoded, receipts)?;
+            transaction.commit().context("commit")
+        }
+        .boxed()
+    }
+
+    fn prepend<'a>(
+        &'a mut self,
+        name: &'a str,
+        filter: &'a database::Filter,
+        logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
     ) -> BoxFuture<'a, Result<()>> {
         async move {
-            let transaction = self.connection.transaction().context("transaction")?;
-            self.inner.update(&transaction, blocks, logs)?;
+            let transaction = self
+                .connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .context("transaction")?;
+            self.inner
+                .update(&transaction, &[], logs, undecoded, receipts)?;
+            self.inner.set_event_filter(&transaction, name, filter)?;
             transaction.commit().context("commit")
         }
         .boxed()
@@ -77,18 +182,49 @@ impl Database for Sqlite {
 
     fn remove<'a>(&'a mut self, uncles: &'a [database::Uncle]) -> BoxFuture<'a, Result<()>> {
         async move {
-            let transaction = self.connection.transaction().context("transaction")?;
+            let transaction = self
+                .connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .context("transaction")?;
             self.inner.remove(&transaction, uncles)?;
             transaction.commit().context("commit")
         }
         .boxed()
     }
+
+    fn replace<'a>(
+        &'a mut self,
+        uncles: &'a [database::Uncle],
+        blocks: &'a [database::EventBlock],
+        logs: &'a [database::Log],
+        undecoded: &'a [database::UndecodedLog],
+        receipts: &'a [database::Receipt],
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let transaction = self
+                .connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .context("transaction")?;
+            self.inner.remove(&transaction, uncles)?;
+            self.inner
+                .update(&transaction, blocks, logs, undecoded, receipts)?;
+            transaction.commit().context("commit")
+        }
+        .boxed()
+    }
 }
 
+/// How long to wait for another connection to finish writing before failing
+/// with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Columns that every event table has.
-const FIXED_COLUMNS: &str = "block_number INTEGER NOT NULL, log_index INTEGER NOT NULL, \
-                             transaction_index INTEGER NOT NULL, address BLOB NOT NULL";
-const FIXED_COLUMNS_COUNT: usize = 4;
+const FIXED_COLUMNS: &str = "block_number INTEGER NOT NULL, block_timestamp INTEGER NOT NULL, \
+                             block_hash BLOB NOT NULL, log_index INTEGER NOT NULL, \
+                             transaction_index INTEGER NOT NULL, transaction_hash BLOB NOT NULL, \
+                             event_ordinal INTEGER NOT NULL, address BLOB NOT NULL, \
+                             contract_label TEXT";
+const FIXED_COLUMNS_COUNT: usize = 9;
 const PRIMARY_KEY: &str = "block_number ASC, log_index ASC";
 
 /// Column for array tables.
@@ -105,6 +241,51 @@ const SET_EVENT_BLOCK: &str =
     "UPDATE _event_block SET indexed = ?2, finalized = ?3 WHERE event = ?1;";
 const SET_INDEXED_BLOCK: &str = "UPDATE _event_block SET indexed = ?2 WHERE event = ?1";
 
+const CREATE_UNDECODED_LOGS_TABLE: &str = "CREATE TABLE IF NOT EXISTS _undecoded_logs(event \
+                                           TEXT NOT NULL, block_number INTEGER NOT NULL, \
+                                           block_hash BLOB NOT NULL, log_index INTEGER NOT NULL, \
+                                           transaction_index INTEGER NOT NULL, transaction_hash \
+                                           BLOB NOT NULL, address BLOB NOT NULL, topics BLOB NOT \
+                                           NULL, data BLOB NOT NULL, error TEXT NOT NULL, \
+                                           PRIMARY KEY(event, block_number, log_index)) STRICT;";
+const INSERT_UNDECODED_LOG: &str = "INSERT INTO _undecoded_logs (event, block_number, block_hash, \
+                                    log_index, transaction_index, transaction_hash, address, \
+                                    topics, data, error) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, \
+                                    ?9, ?10);";
+const REMOVE_UNDECODED_LOGS: &str =
+    "DELETE FROM _undecoded_logs WHERE event = ?1 AND block_number >= ?2;";
+
+const CREATE_TRANSACTIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS _transactions(network \
+                                         TEXT NOT NULL, block_number INTEGER NOT NULL, \
+                                         transaction_index INTEGER NOT NULL, transaction_hash \
+                                         BLOB NOT NULL, from_ BLOB, to_ BLOB, gas_used INTEGER, \
+                                         effective_gas_price INTEGER, status INTEGER, \
+                                         PRIMARY KEY(network, block_number, transaction_index)) \
+                                         STRICT;";
+/// Transactions with logs of multiple events are stored once.
+const INSERT_TRANSACTION: &str = "INSERT INTO _transactions (network, block_number, \
+                                  transaction_index, transaction_hash, from_, to_, gas_used, \
+                                  effective_gas_price, status) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, \
+                                  ?8, ?9) ON CONFLICT(network, block_number, transaction_index) \
+                                  DO NOTHING;";
+
+const CREATE_EVENT_METADATA_TABLE: &str = "CREATE TABLE IF NOT EXISTS _event_metadata(event \
+                                            TEXT PRIMARY KEY NOT NULL, signature TEXT NOT NULL, \
+                                            layout TEXT NOT NULL) STRICT;";
+const GET_EVENT_METADATA: &str = "SELECT signature, layout FROM _event_metadata WHERE event = ?1;";
+const NEW_EVENT_METADATA: &str =
+    "INSERT INTO _event_metadata (event, signature, layout) VALUES(?1, ?2, ?3);";
+
+const CREATE_EVENT_FILTER_TABLE: &str = "CREATE TABLE IF NOT EXISTS _event_filter(event TEXT \
+                                         PRIMARY KEY NOT NULL, fingerprint TEXT NOT NULL, start \
+                                         INTEGER NOT NULL) STRICT;";
+const GET_EVENT_FILTER: &str = "SELECT fingerprint, start FROM _event_filter WHERE event = ?1;";
+const SET_EVENT_FILTER: &str = "INSERT INTO _event_filter (event, fingerprint, start) VALUES(?1, \
+                                ?2, ?3) ON CONFLICT(event) DO UPDATE SET fingerprint = \
+                                excluded.fingerprint, start = excluded.start;";
+
+const TABLE_COLUMNS: &str = "SELECT name, type FROM pragma_table_info(?1);";
+
 const TABLE_EXISTS: &str =
     "SELECT COUNT(*) > 0 FROM sqlite_schema WHERE type = 'table' AND name = ?1";
 
@@ -116,6 +297,8 @@ struct SqliteInner {
     /// The key is the `name` argument when the event was passed into
     /// `prepare_event`.
     events: HashMap<String, PreparedEvent>,
+    /// The network that receipts are stored for.
+    network: String,
 }
 
 /// An event is represented in the database in several tables.
@@ -137,9 +320,16 @@ struct PreparedEvent {
 
 /// Parameters:
 /// - 1: block number
-/// - 2: log index
-/// - 3: array index if this is an array table (all tables after the first)
-/// - 3 + n: n-th event field/column
+/// - 2: block timestamp
+/// - 3: block hash
+/// - 4: log index
+/// - 5: transaction index
+/// - 6: transaction hash
+/// - 7: transaction event index
+/// - 8: address
+/// - 9: contract label
+/// - 10: array index if this is an array table (all tables after the first)
+/// - 10 + n: n-th event field/column
 #[derive(Debug)]
 struct InsertStatement {
     sql: String,
@@ -149,10 +339,22 @@ struct InsertStatement {
 }
 
 impl SqliteInner {
-    fn new(connection: &Connection) -> Result<Self> {
+    fn new(connection: &Connection, network: &str) -> Result<Self> {
         connection
             .execute(CREATE_EVENT_BLOCK_TABLE, ())
             .context("create event_block table")?;
+        connection
+            .execute(CREATE_UNDECODED_LOGS_TABLE, ())
+            .context("create undecoded_logs table")?;
+        connection
+            .execute(CREATE_TRANSACTIONS_TABLE, ())
+            .context("create transactions table")?;
+        connection
+            .execute(CREATE_EVENT_METADATA_TABLE, ())
+            .context("create event_metadata table")?;
+        connection
+            .execute(CREATE_EVENT_FILTER_TABLE, ())
+            .context("create event_filter table")?;
 
         connection
             .prepare_cached(GET_EVENT_BLOCK)
@@ -163,12 +365,22 @@ impl SqliteInner {
         connection
             .prepare_cached(SET_INDEXED_BLOCK)
             .context("prepare set_indexed_block")?;
+        connection
+            .prepare_cached(GET_EVENT_METADATA)
+            .context("prepare get_event_metadata")?;
+        connection
+            .prepare_cached(GET_EVENT_FILTER)
+            .context("prepare get_event_filter")?;
+        connection
+            .prepare_cached(SET_EVENT_FILTER)
+            .context("prepare set_event_filter")?;
         connection
             .prepare_cached(TABLE_EXISTS)
             .context("prepare table_exists")?;
 
         Ok(Self {
             events: Default::default(),
+            network: network.to_owned(),
         })
     }
 
@@ -200,6 +412,71 @@ impl SqliteInner {
         })
     }
 
+    fn event_addresses(
+        &self,
+        con: &Connection,
+        name: &str,
+        field: usize,
+    ) -> Result<Vec<(Address, u64)>> {
+        let prepared = self.events.get(name).context("unprepared event")?;
+        let column = database::event_to_tables::field_column(name, &prepared.descriptor, field)?;
+        let mut statement = con
+            .prepare_cached(&format!(
+                "SELECT {column}, MIN(block_number) FROM {name} GROUP BY {column};"
+            ))
+            .context("prepare_cached")?;
+        let rows = statement
+            .query_map((), |row| {
+                Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, i64>(1)?))
+            })
+            .context("query_map")?;
+        rows.map(|row| {
+            let (address, block) = row.context("row")?;
+            Ok((
+                Address(address.try_into().map_err(|_| anyhow!("invalid address"))?),
+                block.try_into().context("block out of bounds")?,
+            ))
+        })
+        .collect()
+    }
+
+    fn event_filter(&self, con: &Connection, name: &str) -> Result<Option<database::Filter>> {
+        let mut statement = con
+            .prepare_cached(GET_EVENT_FILTER)
+            .context("prepare_cached")?;
+        let filter: Option<(String, i64)> = statement
+            .query_row((name,), |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+            .context("query_row")?;
+        filter
+            .map(|(fingerprint, start)| -> Result<database::Filter> {
+                Ok(database::Filter {
+                    fingerprint,
+                    start: start.try_into().context("start out of bounds")?,
+                })
+            })
+            .transpose()
+    }
+
+    fn set_event_filter(
+        &self,
+        con: &Connection,
+        name: &str,
+        filter: &database::Filter,
+    ) -> Result<()> {
+        if !self.events.contains_key(name) {
+            return Err(anyhow!("event {name} wasn't prepared"));
+        }
+        let start: i64 = filter.start.try_into().context("start out of bounds")?;
+        let mut statement = con
+            .prepare_cached(SET_EVENT_FILTER)
+            .context("prepare_cached")?;
+        statement
+            .execute((name, &filter.fingerprint, start))
+            .context("execute")?;
+        Ok(())
+    }
+
     fn set_event_blocks(&self, con: &Transaction, blocks: &[database::EventBlock]) -> Result<()> {
         let mut statement = con
             .prepare_cached(SET_EVENT_BLOCK)
@@ -237,12 +514,8 @@ impl SqliteInner {
         event: &EventDescriptor,
     ) -> Result<()> {
         // TODO:
-        // - Check that either no table exists or all tables exist and with the right
-        //   types.
         // - Maybe have `CHECK` clauses to enforce things like address and integers
         //   having expected length.
-        // - Maybe store serialized event descriptor in the database so we can load and
-        //   check it.
 
         if let Some(existing) = self.events.get(name) {
             if event != &existing.descriptor {
@@ -258,6 +531,46 @@ impl SqliteInner {
             database::event_to_tables::event_to_tables(name, event).context("unsupported event")?;
         let name = &tables.primary.name;
 
+        let mut existing = Vec::new();
+        for (is_array, table) in std::iter::once((false, &tables.primary))
+            .chain(std::iter::repeat(true).zip(&tables.dynamic_arrays))
+        {
+            let columns = table_columns(con, &table.name)?;
+            metadata::verify_fixed_columns(&table.name, is_array, &columns)?;
+            existing.push((is_array, table, columns));
+        }
+
+        let metadata = Metadata::new(event, &tables);
+        let mut get_event_metadata = con
+            .prepare_cached(GET_EVENT_METADATA)
+            .context("prepare get_event_metadata")?;
+        let stored = get_event_metadata
+            .query_row((name,), |row| {
+                Ok(Metadata {
+                    signature: row.get(0)?,
+                    layout: row.get(1)?,
+                })
+            })
+            .optional()
+            .context("query get_event_metadata")?;
+        match stored {
+            Some(stored) => metadata.verify(name, &stored)?,
+            None => {
+                for (is_array, table, columns) in &existing {
+                    metadata::verify_columns(
+                        &table.name,
+                        columns,
+                        &expected_columns(*is_array, table),
+                    )?;
+                }
+                con.execute(
+                    NEW_EVENT_METADATA,
+                    (name, &metadata.signature, &metadata.layout),
+                )
+                .context("execute new_event_metadata")?;
+            }
+        }
+
         let create_table = |is_array: bool, table: &Table| {
             let mut sql = String::new();
             write!(&mut sql, "CREATE TABLE IF NOT EXISTS {} (", table.name).unwrap();
@@ -266,15 +579,7 @@ impl SqliteInner {
                 write!(&mut sql, "{ARRAY_COLUMN}, ").unwrap();
             }
             for column in table.columns.iter() {
-                write!(&mut sql, "{}", column.name).unwrap();
-                let type_ = match abi_kind_to_sql_type(column.kind).unwrap() {
-                    SqlType::Null => unreachable!(),
-                    SqlType::Integer => "INTEGER",
-                    SqlType::Real => "REAL",
-                    SqlType::Text => "TEXT",
-                    SqlType::Blob => "BLOB",
-                };
-                write!(&mut sql, " {type_}, ").unwrap();
+                write!(&mut sql, "{} {}, ", column.name, column_type(column.kind)).unwrap();
             }
             let primary_key = if is_array {
                 PRIMARY_KEY_ARRAY
@@ -351,9 +656,14 @@ impl SqliteInner {
         Log {
             event,
             block_number,
+            block_timestamp,
+            block_hash,
             log_index,
             transaction_index,
+            transaction_hash,
+            event_ordinal,
             address,
+            contract_label,
             fields,
         }: &'a Log,
     ) -> Result<()> {
@@ -432,10 +742,20 @@ impl SqliteInner {
 
         let block_number =
             ToSqlOutput::Owned(SqlValue::Integer((*block_number).try_into().unwrap()));
+        let block_timestamp =
+            ToSqlOutput::Owned(SqlValue::Integer((*block_timestamp).try_into().unwrap()));
+        let block_hash = ToSqlOutput::Borrowed(SqlValueRef::Blob(&block_hash.0));
         let log_index = ToSqlOutput::Owned(SqlValue::Integer((*log_index).try_into().unwrap()));
         let transaction_index =
             ToSqlOutput::Owned(SqlValue::Integer((*transaction_index).try_into().unwrap()));
+        let transaction_hash = ToSqlOutput::Borrowed(SqlValueRef::Blob(&transaction_hash.0));
+        let event_ordinal =
+            ToSqlOutput::Owned(SqlValue::Integer((*event_ordinal).try_into().unwrap()));
         let address = ToSqlOutput::Borrowed(SqlValueRef::Blob(&address.0));
+        let contract_label = match contract_label {
+            Some(label) => ToSqlOutput::Borrowed(SqlValueRef::Text(label.as_bytes())),
+            None => ToSqlOutput::Owned(SqlValue::Null),
+        };
         for (statement, (array_element_count, values)) in
             event.insert_statements.iter().zip(sql_values)
         {
@@ -453,10 +773,20 @@ impl SqliteInner {
                     None
                 };
                 let params = rusqlite::params_from_iter(
-                    [&block_number, &log_index, &transaction_index, &address]
-                        .into_iter()
-                        .chain(array_index.as_ref())
-                        .chain(row),
+                    [
+                        &block_number,
+                        &block_timestamp,
+                        &block_hash,
+                        &log_index,
+                        &transaction_index,
+                        &transaction_hash,
+                        &event_ordinal,
+                        &address,
+                        &contract_label,
+                    ]
+                    .into_iter()
+                    .chain(array_index.as_ref())
+                    .chain(row),
                 );
                 statement_.insert(params).context("insert")?;
             }
@@ -470,12 +800,78 @@ impl SqliteInner {
         con: &Transaction,
         blocks: &[database::EventBlock],
         logs: &[database::Log],
+        undecoded: &[database::UndecodedLog],
+        receipts: &[database::Receipt],
     ) -> Result<()> {
         self.set_event_blocks(con, blocks)
             .context("set_event_blocks")?;
         for log in logs {
             self.store_event(con, log).context("store_event")?;
         }
+        for log in undecoded {
+            self.store_undecoded(con, log).context("store_undecoded")?;
+        }
+        for receipt in receipts {
+            self.store_receipt(con, receipt).context("store_receipt")?;
+        }
+        Ok(())
+    }
+
+    fn store_receipt(&self, con: &Transaction, receipt: &database::Receipt) -> Result<()> {
+        let mut statement = con
+            .prepare_cached(INSERT_TRANSACTION)
+            .context("prepare_cached")?;
+        statement
+            .execute((
+                &self.network,
+                i64::try_from(receipt.block_number).context("block out of bounds")?,
+                i64::try_from(receipt.transaction_index)
+                    .context("transaction index out of bounds")?,
+                receipt.transaction_hash.0.as_slice(),
+                receipt.from.as_ref().map(|from| from.0.as_slice()),
+                receipt.to.as_ref().map(|to| to.0.as_slice()),
+                receipt
+                    .gas_used
+                    .map(i64::try_from)
+                    .transpose()
+                    .context("gas used out of bounds")?,
+                receipt
+                    .effective_gas_price
+                    .map(i64::try_from)
+                    .transpose()
+                    .context("gas price out of bounds")?,
+                receipt.success,
+            ))
+            .context("execute")?;
+        Ok(())
+    }
+
+    fn store_undecoded(&self, con: &Transaction, log: &database::UndecodedLog) -> Result<()> {
+        if !self.events.contains_key(log.event) {
+            return Err(anyhow!("event {} wasn't prepared", log.event));
+        }
+        let topics = log
+            .topics
+            .iter()
+            .flat_map(|topic| topic.0)
+            .collect::<Vec<_>>();
+        let mut statement = con
+            .prepare_cached(INSERT_UNDECODED_LOG)
+            .context("prepare_cached")?;
+        statement
+            .execute((
+                log.event,
+                i64::try_from(log.block_number).context("block out of bounds")?,
+                log.block_hash.0.as_slice(),
+                i64::try_from(log.log_index).context("log index out of bounds")?,
+                i64::try_from(log.transaction_index).context("transaction index out of bounds")?,
+                log.transaction_hash.0.as_slice(),
+                log.address.0.as_slice(),
+                topics,
+                &log.data,
+                &log.error,
+            ))
+            .context("execute")?;
         Ok(())
     }
 
@@ -483,6 +879,9 @@ impl SqliteInner {
         let mut set_indexed_block = connection
             .prepare_cached(SET_INDEXED_BLOCK)
             .context("prepare_cached set_indexed_block")?;
+        let mut remove_undecoded_logs = connection
+            .prepare_cached(REMOVE_UNDECODED_LOGS)
+            .context("prepare_cached remove_undecoded_logs")?;
         for uncle in uncles {
             if uncle.number == 0 {
                 return Err(anyhow!("block 0 got uncled"));
@@ -501,11 +900,70 @@ impl SqliteInner {
                     .execute((uncle.event, parent_block))
                     .context("execute set_indexed_block")?;
             }
+            remove_undecoded_logs
+                .execute((uncle.event, block))
+                .context("execute remove_undecoded_logs")?;
+        }
+
+        if let Some(block) = uncles.iter().map(|uncle| uncle.number).min() {
+            let block = i64::try_from(block).context("block out of bounds")?;
+            let mut events = self.events.keys().collect::<Vec<_>>();
+            events.sort();
+            let mut remove_transactions = connection
+                .prepare_cached(&database::remove_transactions_sql(&events, "?1", "?2"))
+                .context("prepare_cached remove_transactions")?;
+            remove_transactions
+                .execute((&self.network, block))
+                .context("execute remove_transactions")?;
         }
         Ok(())
     }
 }
 
+/// Returns the names and declared types of the columns of a table in order,
+/// or an empty list if the table doesn't exist.
+fn table_columns(con: &Connection, table: &str) -> Result<Vec<(String, String)>> {
+    let mut statement = con
+        .prepare_cached(TABLE_COLUMNS)
+        .context("prepare_cached table_columns")?;
+    let columns = statement
+        .query_map((table,), |row| Ok((row.get(0)?, row.get(1)?)))
+        .context("query_map table_columns")?;
+    columns.map(|column| column.context("row")).collect()
+}
+
+/// Returns the names and declared types of the columns that an event table is
+/// created with.
+fn expected_columns(is_array: bool, table: &Table) -> Vec<(String, String)> {
+    FIXED_COLUMNS
+        .split(", ")
+        .chain(is_array.then_some(ARRAY_COLUMN))
+        .map(|column| {
+            let mut words = column.split_whitespace();
+            let name = words.next().expect("column name");
+            let type_ = words.next().expect("column type");
+            (name.to_string(), type_.to_string())
+        })
+        .chain(
+            table
+                .columns
+                .iter()
+                .map(|column| (column.name.clone(), column_type(column.kind).to_string())),
+        )
+        .collect()
+}
+
+/// Returns the declared type of the column that an event field is stored in.
+fn column_type(kind: &AbiKind) -> &'static str {
+    match abi_kind_to_sql_type(kind).unwrap() {
+        SqlType::Null => unreachable!(),
+        SqlType::Integer => "INTEGER",
+        SqlType::Real => "REAL",
+        SqlType::Text => "TEXT",
+        SqlType::Blob => "BLOB",
+    }
+}
+
 fn abi_kind_to_sql_type(value: &AbiKind) -> Option<SqlType> {
     match value {
         AbiKind::Int(_) => Some(SqlType::Blob),
@@ -525,7 +983,7 @@ mod tests {
     use {
         super::*,
         solabi::{
-            ethprim::Address,
+            ethprim::{Address, Digest},
             function::{ExternalFunction, Selector},
             value::{Array, FixedBytes, Int, Uint},
         },
@@ -536,6 +994,34 @@ mod tests {
         Sqlite::new_for_test();
     }
 
+    #[tokio::test]
+    async fn waits_for_concurrent_writers() {
+        let path = std::env::temp_dir().join(format!("arak-test-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut first = Sqlite::open(path, "first").unwrap();
+        let mut second = Sqlite::open(path, "second").unwrap();
+
+        let (locked, wait) = std::sync::mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            let transaction = first
+                .connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .unwrap();
+            locked.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            transaction.commit().unwrap();
+        });
+        wait.recv().unwrap();
+        let event = EventDescriptor::parse_declaration("event Event(uint256 a)").unwrap();
+        let result = second.prepare_event("event", &event).await;
+        writer.join().unwrap();
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{path}{suffix}"));
+        }
+        result.unwrap();
+    }
+
     fn print_table(con: &Connection, table: &str) {
         let mut statement = con.prepare(&format!("SELECT * FROM {table}")).unwrap();
         let mut rows = statement.query(()).unwrap();
@@ -586,11 +1072,18 @@ event Event (
                 &[Log {
                     event: "event",
                     block_number: 1,
+                    block_timestamp: 8,
+                    block_hash: Digest([5; 32]),
                     log_index: 2,
                     transaction_index: 3,
+                    transaction_hash: Digest([6; 32]),
+                    event_ordinal: 7,
                     address: Address([4; 20]),
+                    contract_label: Some("pool"),
                     fields,
                 }],
+                &[],
+                &[],
             )
             .await
             .unwrap();
@@ -598,6 +1091,50 @@ event Event (
         print_table(&sqlite.connection, "event");
     }
 
+    #[tokio::test]
+    async fn fixed_columns() {
+        let mut sqlite = Sqlite::new_for_test();
+        let event = EventDescriptor::parse_declaration("event Event(bool)").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+
+        let log = Log {
+            event: "event",
+            block_number: 1,
+            block_timestamp: 8,
+            block_hash: Digest([2; 32]),
+            log_index: 3,
+            transaction_index: 4,
+            transaction_hash: Digest([5; 32]),
+            event_ordinal: 6,
+            address: Address([7; 20]),
+            contract_label: Some("pool"),
+            fields: vec![AbiValue::Bool(true)],
+        };
+        sqlite.update(&[], &[log], &[], &[]).await.unwrap();
+
+        let row: (i64, Vec<u8>, Vec<u8>, i64, Option<String>) = sqlite
+            .connection
+            .query_row(
+                "SELECT block_timestamp, block_hash, transaction_hash, event_ordinal, \
+                 contract_label FROM event",
+                (),
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (8, vec![2; 32], vec![5; 32], 6, Some("pool".to_string()))
+        );
+    }
+
     #[tokio::test]
     async fn with_array() {
         let mut sqlite = Sqlite::new_for_test();
@@ -627,7 +1164,7 @@ event Event (
             )],
             ..Default::default()
         };
-        sqlite.update(&[], &[log]).await.unwrap();
+        sqlite.update(&[], &[log], &[], &[]).await.unwrap();
 
         let log = Log {
             event: "event",
@@ -637,7 +1174,7 @@ event Event (
             )],
             ..Default::default()
         };
-        sqlite.update(&[], &[log]).await.unwrap();
+        sqlite.update(&[], &[log], &[], &[]).await.unwrap();
 
         print_table(&sqlite.connection, "event");
         print_table(&sqlite.connection, "event_array_0");
@@ -658,7 +1195,7 @@ event Event (
                 finalized: 3,
             },
         };
-        sqlite.update(&[blocks], &[]).await.unwrap();
+        sqlite.update(&[blocks], &[], &[], &[]).await.unwrap();
         let result = sqlite.event_block("event").await.unwrap();
         assert_eq!(result.indexed, 2);
         assert_eq!(result.finalized, 3);
@@ -696,6 +1233,8 @@ event Event (
                         ..Default::default()
                     },
                 ],
+                &[],
+                &[],
             )
             .await
             .unwrap();
@@ -736,4 +1275,287 @@ event Event (
             .unwrap();
         assert_eq!(rows(&sqlite), 0);
     }
+
+    #[tokio::test]
+    async fn replace() {
+        let mut sqlite = Sqlite::new_for_test();
+
+        let event = EventDescriptor::parse_declaration("event Event()").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+        let log = |block_number: u64, block_hash: u8| Log {
+            event: "event",
+            block_number,
+            block_hash: Digest([block_hash; 32]),
+            ..Default::default()
+        };
+        let block = |indexed: u64| database::EventBlock {
+            event: "event",
+            block: database::Block {
+                indexed,
+                finalized: 0,
+            },
+        };
+        sqlite
+            .update(&[block(2)], &[log(1, 1), log(2, 1)], &[], &[])
+            .await
+            .unwrap();
+
+        sqlite
+            .replace(
+                &[database::Uncle {
+                    event: "event",
+                    number: 2,
+                }],
+                &[block(3)],
+                &[log(2, 2), log(3, 2)],
+                &[],
+                &[],
+            )
+            .await
+            .unwrap();
+
+        let hashes = sqlite
+            .connection
+            .prepare("SELECT block_number, block_hash FROM event ORDER BY block_number")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<Vec<(u64, Vec<u8>)>, _>>()
+            .unwrap();
+        assert_eq!(
+            hashes,
+            [(1, vec![1; 32]), (2, vec![2; 32]), (3, vec![2; 32])]
+        );
+        assert_eq!(sqlite.event_block("event").await.unwrap().indexed, 3);
+    }
+
+    #[tokio::test]
+    async fn event_addresses() {
+        let mut sqlite = Sqlite::new_for_test();
+
+        let event =
+            EventDescriptor::parse_declaration("event Event(uint256 a, address b)").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+        let log = |block_number: u64, address: u8| Log {
+            event: "event",
+            block_number,
+            fields: vec![
+                AbiValue::Uint(Uint::new(256, 0u32.into()).unwrap()),
+                AbiValue::Address(Address([address; 20])),
+            ],
+            ..Default::default()
+        };
+        sqlite
+            .update(&[], &[log(1, 1), log(2, 2), log(3, 1)], &[], &[])
+            .await
+            .unwrap();
+
+        let mut addresses = sqlite.event_addresses("event", 1).await.unwrap();
+        addresses.sort_by_key(|(_, block)| *block);
+        assert_eq!(addresses, [(Address([1; 20]), 1), (Address([2; 20]), 2)]);
+    }
+
+    #[tokio::test]
+    async fn undecoded_logs() {
+        let mut sqlite = Sqlite::new_for_test();
+
+        let event = EventDescriptor::parse_declaration("event Event()").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+        let log = |block_number: u64| database::UndecodedLog {
+            event: "event",
+            block_number,
+            topics: vec![Digest([1; 32]), Digest([2; 32])],
+            data: vec![3; 4],
+            error: "invalid data".to_string(),
+            ..Default::default()
+        };
+        sqlite
+            .update(&[], &[], &[log(1), log(2)], &[])
+            .await
+            .unwrap();
+
+        sqlite
+            .remove(&[database::Uncle {
+                event: "event",
+                number: 2,
+            }])
+            .await
+            .unwrap();
+
+        let rows = sqlite
+            .connection
+            .prepare("SELECT block_number, topics, data, error FROM _undecoded_logs")
+            .unwrap()
+            .query_map((), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<Vec<(u64, Vec<u8>, Vec<u8>, String)>, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [(
+                1,
+                [[1; 32], [2; 32]].concat(),
+                vec![3; 4],
+                "invalid data".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn transactions() {
+        let mut sqlite = Sqlite::new_for_test();
+
+        let event = EventDescriptor::parse_declaration("event Event()").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+        let other = EventDescriptor::parse_declaration("event Other()").unwrap();
+        sqlite.prepare_event("other", &other).await.unwrap();
+        let receipt = |block_number: u64, to: Option<Address>| database::Receipt {
+            block_number,
+            transaction_index: 1,
+            from: Some(Address([1; 20])),
+            to,
+            gas_used: Some(21_000),
+            success: Some(true),
+            ..Default::default()
+        };
+        // The transaction in block 3 also emitted a log of another event.
+        sqlite
+            .update(
+                &[],
+                &[Log {
+                    event: "other",
+                    block_number: 3,
+                    transaction_index: 1,
+                    ..Default::default()
+                }],
+                &[],
+                &[
+                    receipt(1, Some(Address([2; 20]))),
+                    receipt(2, None),
+                    receipt(3, None),
+                    receipt(3, None),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(sqlite.transactions(), [(1, 1), (2, 1), (3, 1)]);
+
+        sqlite
+            .remove(&[database::Uncle {
+                event: "event",
+                number: 2,
+            }])
+            .await
+            .unwrap();
+        assert_eq!(sqlite.transactions(), [(1, 1), (3, 1)]);
+
+        type Row = (Vec<u8>, Option<Vec<u8>>, u64, Option<u64>, Option<bool>);
+        let rows = sqlite
+            .connection
+            .prepare(
+                "SELECT from_, to_, gas_used, effective_gas_price, status FROM _transactions \
+                 WHERE block_number = 1",
+            )
+            .unwrap()
+            .query_map((), |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<Row>, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [(vec![1; 20], Some(vec![2; 20]), 21_000, None, Some(true))]
+        );
+    }
+
+    #[tokio::test]
+    async fn transactions_of_networks() {
+        let mut sqlite = Sqlite::new_for_test();
+        let receipt = database::Receipt {
+            block_number: 1,
+            transaction_index: 1,
+            ..Default::default()
+        };
+        let event = EventDescriptor::parse_declaration("event Event()").unwrap();
+        for network in ["a", "b"] {
+            sqlite.inner = SqliteInner::new(&sqlite.connection, network).unwrap();
+            let name = format!("{network}_event");
+            sqlite.prepare_event(&name, &event).await.unwrap();
+            sqlite
+                .update(&[], &[], &[], std::slice::from_ref(&receipt))
+                .await
+                .unwrap();
+        }
+        assert_eq!(sqlite.transactions(), [(1, 1), (1, 1)]);
+
+        // Removing blocks of one network keeps the receipts of the others.
+        sqlite
+            .remove(&[database::Uncle {
+                event: "b_event",
+                number: 1,
+            }])
+            .await
+            .unwrap();
+        assert_eq!(sqlite.transactions(), [(1, 1)]);
+    }
+
+    #[tokio::test]
+    async fn verifies_persisted_events() {
+        let mut sqlite = Sqlite::new_for_test();
+        let event = EventDescriptor::parse_declaration("event Event(uint256 a)").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+
+        // Simulate a restart by forgetting about the prepared events.
+        sqlite.inner = SqliteInner::new(&sqlite.connection, "").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+
+        sqlite.inner = SqliteInner::new(&sqlite.connection, "").unwrap();
+        let changed = EventDescriptor::parse_declaration("event Event(address a)").unwrap();
+        assert!(sqlite.prepare_event("event", &changed).await.is_err());
+        sqlite.prepare_event("other", &changed).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_outdated_tables() {
+        let mut sqlite = Sqlite::new_for_test();
+        sqlite
+            .connection
+            .execute(
+                "CREATE TABLE event (block_number INTEGER NOT NULL, log_index INTEGER NOT NULL, \
+                 a_0 BLOB) STRICT;",
+                (),
+            )
+            .unwrap();
+        let event = EventDescriptor::parse_declaration("event Event(uint256 a)").unwrap();
+        assert!(sqlite.prepare_event("event", &event).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn verifies_tables_without_metadata() {
+        let mut sqlite = Sqlite::new_for_test();
+        let event = EventDescriptor::parse_declaration("event Event(uint256 a)").unwrap();
+        sqlite.prepare_event("event", &event).await.unwrap();
+
+        // Tables created before metadata was recorded are checked against the
+        // configured event.
+        sqlite
+            .connection
+            .execute("DELETE FROM _event_metadata;", ())
+            .unwrap();
+        let mut sqlite = Sqlite::new(sqlite.connection, "").unwrap();
+        let other = EventDescriptor::parse_declaration("event Event(bool a)").unwrap();
+        assert!(sqlite.prepare_event("event", &other).await.is_err());
+        let other = EventDescriptor::parse_declaration("event Event(uint256 b)").unwrap();
+        assert!(sqlite.prepare_event("event", &other).await.is_err());
+        sqlite.prepare_event("event", &event).await.unwrap();
+    }
 }
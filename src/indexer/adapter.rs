@@ -4,16 +4,23 @@
 //! - Decode Ethereum log topics and data into Solidity values
 
 use {
+    super::filter,
     crate::config,
-    anyhow::{Context, Result},
+    anyhow::{ensure, Result},
     ethrpc::types::{ArrayVec, Digest, LogBlocks, LogFilter, LogFilterValue},
     solabi::{
         abi::EventDescriptor,
+        ethprim::Address,
         value::{EventEncoder, Value},
     },
-    std::borrow::Cow,
+    std::{borrow::Cow, collections::HashMap, fmt::Display},
 };
 
+/// The maximum number of discovered contracts to query logs for with a single
+/// filter. Nodes limit the size of requests, so events of factories with many
+/// children are queried with multiple filters.
+const MAX_FILTER_ADDRESSES: usize = 1000;
+
 /// An [`Adapter`] is an adapter for a single event. Here's an example image of
 /// an [`Adapter`]. Its purpose is to adapt the single event.
 /// https://www.bhphotovideo.com/images/images2500x2500/hp_as615at_displayport_to_vga_adapter_1024540.jpg
@@ -21,27 +28,63 @@ pub struct Adapter {
     name: String,
     signature: EventDescriptor,
     start: u64,
+    /// The last block to index, or `None` to index indefinitely.
+    end: Option<u64>,
     filter: LogFilter,
+    labels: HashMap<Address, String>,
+    factory: Option<config::Factory>,
+    /// Contracts discovered from the factory event along with the blocks they
+    /// were created in.
+    children: HashMap<Address, u64>,
     encoder: EventEncoder,
+    receipt: bool,
 }
 
 impl Adapter {
     /// Creates a new adapter for a single event.
+    ///
+    /// Anonymous events don't have a selector topic, so all topics are indexed
+    /// fields. Since they can't be told apart from other events by their
+    /// topics, they require a contract address.
     pub fn new(config: config::Event) -> Result<Self> {
+        let mut labels = HashMap::new();
+        let mut factory = None;
         let filter = LogFilter {
             address: match config.contract {
-                config::Contract::All => LogFilterValue::Any,
+                config::Contract::All => {
+                    ensure!(
+                        !config.signature.anonymous,
+                        "anonymous events require a contract address"
+                    );
+                    LogFilterValue::Any
+                }
                 config::Contract::Address(address) => LogFilterValue::Exact(address),
+                config::Contract::Factory(config) => {
+                    factory = Some(config);
+                    // Set when creating filters for the discovered children.
+                    LogFilterValue::Any
+                }
+                contract => match contract.addresses(&mut labels)? {
+                    Some(addresses) => LogFilterValue::OneOf(addresses),
+                    None => LogFilterValue::Any,
+                },
             },
             topics: {
                 let mut topics = ArrayVec::<_, 4>::new();
-                topics.try_push(LogFilterValue::Exact(Digest(
-                    config
-                        .signature
-                        .selector()
-                        .context("anonymous events are not supported")?,
-                )))?;
-                topics.extend(config.topics);
+                if let Some(selector) = config.signature.selector() {
+                    topics.try_push(LogFilterValue::Exact(Digest(selector)))?;
+                }
+                let filters = if config.filter.is_empty() {
+                    config.topics.into_iter().collect()
+                } else {
+                    ensure!(
+                        config.topics.is_empty(),
+                        "raw topics and field filters can't be used together"
+                    );
+                    filter::topics(&config.signature, &config.filter)?
+                };
+                ensure!(topics.len() + filters.len() <= 4, "too many topic filters");
+                topics.extend(filters);
                 topics
             },
             blocks: LogBlocks::default(),
@@ -52,8 +95,13 @@ impl Adapter {
             name: config.name,
             signature: config.signature,
             start: config.start,
+            end: config.end,
             filter,
+            labels,
+            factory,
+            children: HashMap::new(),
             encoder,
+            receipt: config.receipt,
         })
     }
 
@@ -77,14 +125,96 @@ impl Adapter {
         self.start
     }
 
-    /// Returns a log filter for the specified blocks.
-    pub fn filter(&self, blocks: LogBlocks) -> LogFilter {
+    /// Returns the last block to index events for, if any.
+    pub fn end(&self) -> Option<u64> {
+        self.end
+    }
+
+    /// Returns `true` if the receipts of the transactions that emitted the
+    /// event are stored.
+    pub fn receipt(&self) -> bool {
+        self.receipt
+    }
+
+    /// Returns a description of the contracts and topics that the event is
+    /// indexed for. It changes whenever the configuration changes which logs
+    /// match the event.
+    pub fn fingerprint(&self) -> String {
+        let contract = match &self.factory {
+            Some(factory) => format!("{}.{}", factory.from_event, factory.field),
+            None => fingerprint(&self.filter.address),
+        };
+        let topics = self
+            .filter
+            .topics
+            .iter()
+            .map(fingerprint)
+            .collect::<Vec<_>>();
+        format!("contract = {contract}; topics = [{}]", topics.join(", "))
+    }
+
+    /// Returns the log filters for the specified blocks. Events of
+    /// factory-discovered contracts have one filter per chunk of at most
+    /// [`MAX_FILTER_ADDRESSES`] discovered contracts, and none when no
+    /// contracts have been discovered yet, as there is nothing to query.
+    pub fn filters(&self, blocks: LogBlocks) -> Vec<LogFilter> {
+        if self.factory.is_none() {
+            return vec![LogFilter {
+                blocks,
+                ..self.filter.clone()
+            }];
+        }
+        let mut children = self.children.keys().copied().collect::<Vec<_>>();
+        children.sort();
+        children
+            .chunks(MAX_FILTER_ADDRESSES)
+            .map(|addresses| self.contracts_filter(blocks.clone(), addresses.to_vec()))
+            .collect()
+    }
+
+    /// Returns a log filter for the specified blocks and contracts.
+    pub fn contracts_filter(&self, blocks: LogBlocks, addresses: Vec<Address>) -> LogFilter {
         LogFilter {
             blocks,
+            address: LogFilterValue::OneOf(addresses),
             ..self.filter.clone()
         }
     }
 
+    /// Returns the factory event configuration for events of
+    /// factory-discovered contracts.
+    pub fn factory(&self) -> Option<&config::Factory> {
+        self.factory.as_ref()
+    }
+
+    /// Returns the block that a contract discovered from the factory event was
+    /// created in, or `None` if the contract is not known.
+    pub fn child(&self, address: &Address) -> Option<u64> {
+        self.children.get(address).copied()
+    }
+
+    /// Sets the contracts discovered from the factory event along with their
+    /// creation blocks.
+    pub fn set_children(&mut self, children: impl IntoIterator<Item = (Address, u64)>) {
+        self.children = children.into_iter().collect();
+    }
+
+    /// Adds a contract discovered from the factory event.
+    pub fn add_child(&mut self, address: Address, block: u64) {
+        self.children.insert(address, block);
+    }
+
+    /// Removes discovered contracts that were created in the specified block
+    /// or later, because their creation was reorged.
+    pub fn remove_children(&mut self, block: u64) {
+        self.children.retain(|_, created| *created < block);
+    }
+
+    /// Returns the configured label for a contract address, if any.
+    pub fn contract_label(&self, address: &Address) -> Option<&str> {
+        self.labels.get(address).map(String::as_str)
+    }
+
     /// Decodes Ethereum log topics and data into a database event for storing.
     pub fn decode(&self, topics: &[Digest], data: &[u8]) -> Result<Vec<Value>> {
         let fields = self.encoder.decode(&solabi::log::Log {
@@ -101,6 +231,20 @@ impl Adapter {
     }
 }
 
+/// Returns a description of a log filter value that doesn't depend on the
+/// order of the values it matches.
+fn fingerprint<T: Display>(value: &LogFilterValue<T>) -> String {
+    match value {
+        LogFilterValue::Any => "*".to_string(),
+        LogFilterValue::Exact(value) => value.to_string(),
+        LogFilterValue::OneOf(values) => {
+            let mut values = values.iter().map(T::to_string).collect::<Vec<_>>();
+            values.sort();
+            values.join(" | ")
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
@@ -113,8 +257,154 @@ mod tests {
     };
 
     #[test]
-    fn no_anonymous_events() {
-        assert!(Adapter::new(config::Event::for_signature("event Foo() anonymous;")).is_err());
+    fn anonymous_events_require_contract() {
+        let mut event = config::Event::for_signature("event Foo(uint256 indexed a) anonymous;");
+        assert!(Adapter::new(event.clone()).is_err());
+
+        event.contract =
+            config::Contract::Address(address!("0x0101010101010101010101010101010101010101"));
+        let adapter = Adapter::new(event).unwrap();
+        assert!(adapter.filters(LogBlocks::default())[0].topics.is_empty());
+    }
+
+    #[test]
+    fn labeled_contracts() {
+        let pool = address!("0x0101010101010101010101010101010101010101");
+        let vault = address!("0x0202020202020202020202020202020202020202");
+
+        let mut event = config::Event::for_signature("event Foo()");
+        event.contract = config::Contract::Labeled(
+            [("pool".to_string(), pool), ("vault".to_string(), vault)]
+                .into_iter()
+                .collect(),
+        );
+        let adapter = Adapter::new(event).unwrap();
+
+        assert!(matches!(
+            &adapter.filters(LogBlocks::default())[..],
+            [LogFilter { address: LogFilterValue::OneOf(addresses), .. }]
+                if addresses == &[pool, vault]
+        ));
+        assert_eq!(adapter.contract_label(&pool), Some("pool"));
+        assert_eq!(adapter.contract_label(&vault), Some("vault"));
+        assert_eq!(adapter.contract_label(&Address::default()), None);
+    }
+
+    #[test]
+    fn fingerprints_ignore_contract_order() {
+        let pool = address!("0x0101010101010101010101010101010101010101");
+        let vault = address!("0x0202020202020202020202020202020202020202");
+
+        let mut event = config::Event::for_signature("event Foo()");
+        event.contract = config::Contract::Addresses(vec![pool, vault]);
+        let fingerprint = Adapter::new(event.clone()).unwrap().fingerprint();
+
+        event.contract = config::Contract::Addresses(vec![vault, pool]);
+        assert_eq!(
+            Adapter::new(event.clone()).unwrap().fingerprint(),
+            fingerprint
+        );
+
+        event.contract = config::Contract::Address(pool);
+        assert_ne!(Adapter::new(event).unwrap().fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn factory_children() {
+        let pool = address!("0x0101010101010101010101010101010101010101");
+
+        let mut event = config::Event::for_signature("event Swap()");
+        event.contract = config::Contract::Factory(config::Factory {
+            from_event: "PoolCreated".to_string(),
+            field: "pool".to_string(),
+        });
+        let mut adapter = Adapter::new(event).unwrap();
+        assert!(adapter.filters(LogBlocks::default()).is_empty());
+
+        adapter.add_child(pool, 5);
+        assert_eq!(adapter.child(&pool), Some(5));
+        assert!(matches!(
+            &adapter.filters(LogBlocks::default())[..],
+            [LogFilter { address: LogFilterValue::OneOf(addresses), .. }] if addresses == &[pool]
+        ));
+
+        adapter.remove_children(5);
+        assert_eq!(adapter.child(&pool), None);
+        assert!(adapter.filters(LogBlocks::default()).is_empty());
+    }
+
+    #[test]
+    fn chunks_factory_children() {
+        let mut event = config::Event::for_signature("event Swap()");
+        event.contract = config::Contract::Factory(config::Factory {
+            from_event: "PoolCreated".to_string(),
+            field: "pool".to_string(),
+        });
+        let mut adapter = Adapter::new(event).unwrap();
+        adapter.set_children((0..MAX_FILTER_ADDRESSES as u64 * 2 + 1).map(|i| {
+            let mut address = Address::default();
+            address.0[12..].copy_from_slice(&i.to_be_bytes());
+            (address, 1)
+        }));
+
+        let sizes = adapter
+            .filters(LogBlocks::default())
+            .into_iter()
+            .map(|filter| match filter.address {
+                LogFilterValue::OneOf(addresses) => addresses.len(),
+                _ => panic!("expected address list"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [MAX_FILTER_ADDRESSES, MAX_FILTER_ADDRESSES, 1]);
+    }
+
+    #[test]
+    fn topic_filters() {
+        let topic = || LogFilterValue::Exact(Digest::default());
+
+        let mut event = config::Event::for_signature(
+            "event Foo(uint256 indexed a, uint256 indexed b, uint256 indexed c, uint256 indexed \
+             d) anonymous;",
+        );
+        event.contract = config::Contract::Address(Default::default());
+        event.topics = [topic(), topic(), topic(), topic()].into_iter().collect();
+        assert_eq!(
+            Adapter::new(event).unwrap().filters(LogBlocks::default())[0]
+                .topics
+                .len(),
+            4
+        );
+
+        let mut event = config::Event::for_signature(
+            "event Foo(uint256 indexed a, uint256 indexed b, uint256 indexed c)",
+        );
+        event.topics = [topic(), topic(), topic(), topic()].into_iter().collect();
+        assert!(Adapter::new(event).is_err());
+    }
+
+    #[test]
+    fn decode_anonymous_event() {
+        let mut event = config::Event::for_signature(
+            "event Deposit(address indexed owner, uint256 indexed id, uint256 amount) anonymous;",
+        );
+        event.contract = config::Contract::Address(Default::default());
+        let adapter = Adapter::new(event).unwrap();
+
+        // The first topic is the first indexed field instead of a selector.
+        let topics = [
+            digest!("0x0000000000000000000000000101010101010101010101010101010101010101"),
+            digest!("0x000000000000000000000000000000000000000000000000000000000000002a"),
+        ];
+        let data = hex!("0000000000000000000000000000000000000000000000000000000000000001");
+
+        assert_eq!(
+            adapter.decode(&topics, &data).unwrap(),
+            [
+                Value::Address(address!("0x0101010101010101010101010101010101010101")),
+                Value::Uint(Uint::new(256, uint!("42")).unwrap()),
+                Value::Uint(Uint::new(256, uint!("1")).unwrap()),
+            ]
+        );
     }
 
     #[test]
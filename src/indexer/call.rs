@@ -0,0 +1,431 @@
+//! An adapter for calls to a single function, or for value transfers. Calls
+//! are matched against the call frames of a block by their target contract
+//! and selector, and their calldata is decoded into the function's arguments.
+//! Call frames are either the transactions of the block or, when tracing, all
+//! calls made in the block including internal ones.
+//!
+//! Calls are stored like event logs, with the call's sender, value and success
+//! status as additional leading fields. This way they are mapped to tables the
+//! same way as events. Value transfers are stored with their sender, recipient
+//! and value.
+
+use {
+    super::node::{Frame, Header},
+    crate::{config, database},
+    anyhow::{ensure, Result},
+    ethrpc::types::Digest,
+    solabi::{
+        abi::{EventDescriptor, EventField},
+        ethprim::{Address, U256},
+        value::{FunctionEncoder, Uint, Value},
+    },
+    std::collections::{HashMap, HashSet},
+};
+
+/// The declaration of the call fields that are stored before the decoded
+/// function arguments.
+const CALL_FIELDS: &str = "event Call(address sender, uint256 value, bool success)";
+
+/// The declaration of the fields that value transfers are stored with.
+const TRANSFER_FIELDS: &str = "event Transfer(address from, address to, uint256 value)";
+
+/// An adapter for calls to a single function, or for value transfers.
+pub struct CallAdapter {
+    name: String,
+    start: u64,
+    /// The last block to index, or `None` to index indefinitely.
+    end: Option<u64>,
+    /// The contracts to index calls to, or `None` for calls to any contract.
+    /// Transfers are indexed if either their sender or recipient is one of
+    /// the contracts.
+    contracts: Option<HashSet<Address>>,
+    labels: HashMap<Address, String>,
+    kind: Kind,
+    /// The event signature that calls are stored with.
+    signature: EventDescriptor,
+}
+
+enum Kind {
+    Function {
+        selector: [u8; 4],
+        encoder: FunctionEncoder,
+        /// Whether calls made by contracts are indexed as well.
+        internal: bool,
+    },
+    Transfer,
+}
+
+/// A matched call, along with the block it was made in.
+#[derive(Clone, Debug)]
+pub struct Call {
+    /// The index of the adapter that the call was matched for.
+    pub adapter: usize,
+    pub header: Header,
+    pub frame: Frame,
+}
+
+impl CallAdapter {
+    /// Creates a new adapter for calls to a single function.
+    pub fn new(config: config::Call) -> Result<Self> {
+        let mut labels = HashMap::new();
+        let contracts = contracts(config.contract, &mut labels)?;
+
+        let mut signature =
+            EventDescriptor::parse_declaration(CALL_FIELDS).expect("invalid call fields");
+        signature.name = config.signature.name.clone();
+        signature
+            .inputs
+            .extend(config.signature.inputs.iter().map(|field| EventField {
+                field: field.clone(),
+                indexed: false,
+            }));
+
+        Ok(Self {
+            name: config.name,
+            start: config.start,
+            end: config.end,
+            contracts,
+            labels,
+            kind: Kind::Function {
+                selector: config.signature.selector().0,
+                encoder: FunctionEncoder::new(&config.signature)?,
+                internal: config.internal,
+            },
+            signature,
+        })
+    }
+
+    /// Creates a new adapter for value transfers.
+    pub fn transfer(config: config::Transfer) -> Result<Self> {
+        let mut labels = HashMap::new();
+        let contracts = contracts(config.contract, &mut labels)?;
+        Ok(Self {
+            name: config.name,
+            start: config.start,
+            end: config.end,
+            contracts,
+            labels,
+            kind: Kind::Transfer,
+            signature: EventDescriptor::parse_declaration(TRANSFER_FIELDS)
+                .expect("invalid transfer fields"),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn for_signature(signature: &str) -> Self {
+        CallAdapter::new(config::Call::for_signature(signature)).unwrap()
+    }
+
+    /// Returns the name of the call indexer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the event signature that calls are stored with. For function
+    /// calls, its fields are the call's sender, value and success status
+    /// followed by the function's arguments.
+    pub fn signature(&self) -> &EventDescriptor {
+        &self.signature
+    }
+
+    /// Returns the first block to index calls for.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the last block to index calls for, if any.
+    pub fn end(&self) -> Option<u64> {
+        self.end
+    }
+
+    /// Returns a description of the contracts that calls or transfers are
+    /// indexed for, and whether internal calls are included.
+    pub fn fingerprint(&self) -> String {
+        let contract = match &self.contracts {
+            Some(contracts) => {
+                let mut contracts = contracts.iter().map(Address::to_string).collect::<Vec<_>>();
+                contracts.sort();
+                contracts.join(" | ")
+            }
+            None => "*".to_string(),
+        };
+        match &self.kind {
+            Kind::Function { internal, .. } => {
+                format!("contract = {contract}; internal = {internal}")
+            }
+            Kind::Transfer => format!("contract = {contract}"),
+        }
+    }
+
+    /// Returns `true` if the adapter needs traces, because it indexes calls
+    /// made by contracts.
+    pub fn traced(&self) -> bool {
+        match &self.kind {
+            Kind::Function { internal, .. } => *internal,
+            Kind::Transfer => true,
+        }
+    }
+
+    /// Returns `true` if the adapter indexes the call.
+    pub fn matches(&self, frame: &Frame) -> bool {
+        let contract = |address: Option<&Address>| match (&self.contracts, address) {
+            (None, _) => true,
+            (Some(contracts), Some(address)) => contracts.contains(address),
+            (Some(_), None) => false,
+        };
+        match &self.kind {
+            Kind::Function {
+                selector, internal, ..
+            } => {
+                frame.to.is_some()
+                    && (*internal || frame.depth == 0)
+                    && frame.input.get(..4) == Some(&selector[..])
+                    && contract(frame.to.as_ref())
+            }
+            Kind::Transfer => {
+                frame.success
+                    && frame.value > U256::ZERO
+                    && (contract(Some(&frame.from)) || contract(frame.to.as_ref()))
+            }
+        }
+    }
+
+    /// Decodes a call into the fields of the adapter's event signature.
+    pub fn decode(&self, frame: &Frame) -> Result<Vec<Value>> {
+        let value = Value::Uint(Uint::new(256, frame.value).expect("invalid uint256"));
+        match &self.kind {
+            Kind::Function { encoder, .. } => {
+                let mut fields = vec![
+                    Value::Address(frame.from),
+                    value,
+                    Value::Bool(frame.success),
+                ];
+                fields.extend(encoder.decode_params(&frame.input)?);
+                Ok(fields)
+            }
+            Kind::Transfer => Ok(vec![
+                Value::Address(frame.from),
+                Value::Address(frame.to.unwrap_or_default()),
+                value,
+            ]),
+        }
+    }
+
+    /// Returns the configured label for a contract address, if any.
+    fn contract_label(&self, address: &Address) -> Option<&str> {
+        self.labels.get(address).map(String::as_str)
+    }
+}
+
+/// Returns the contracts to index calls for, and collects their labels.
+fn contracts(
+    contract: config::Contract,
+    labels: &mut HashMap<Address, String>,
+) -> Result<Option<HashSet<Address>>> {
+    ensure!(
+        !matches!(contract, config::Contract::Factory(_)),
+        "calls to factory-discovered contracts are not supported"
+    );
+    let addresses = contract.addresses(labels)?;
+    Ok(addresses.map(|addresses| addresses.into_iter().collect()))
+}
+
+/// Finds the calls for each adapter in the call frames of a block. `ranges`
+/// contains the inclusive block ranges to index calls in for each adapter.
+pub fn find(
+    adapters: &[CallAdapter],
+    ranges: &[(usize, u64, u64)],
+    header: &Header,
+    frames: &[Frame],
+) -> Vec<Call> {
+    let number = header.number.as_u64();
+    let mut calls = Vec::new();
+    for frame in frames {
+        for &(adapter, from, to) in ranges {
+            if (from..=to).contains(&number) && adapters[adapter].matches(frame) {
+                calls.push(Call {
+                    adapter,
+                    header: *header,
+                    frame: frame.clone(),
+                });
+            }
+        }
+    }
+    calls
+}
+
+/// Converts calls to database logs. Calls are stored with the position of
+/// their frame in the block as log index, which is the transaction index for
+/// adapters that don't need traces. Calls that fail to decode are skipped,
+/// unless decoding errors are fatal.
+pub fn database_logs(
+    adapters: &[CallAdapter],
+    calls: Vec<Call>,
+    fatal_decode_errors: bool,
+) -> Result<Vec<database::Log<'_>>> {
+    let mut logs = Vec::new();
+    // Calls are in order, so we can count each adapter's calls per
+    // transaction as we go.
+    let mut transaction_calls = HashMap::<(usize, Digest), u64>::new();
+    for call in calls {
+        let adapter = &adapters[call.adapter];
+        let ordinal = transaction_calls
+            .entry((call.adapter, call.frame.transaction_hash))
+            .or_default();
+        let index = *ordinal;
+        *ordinal += 1;
+
+        let fields = match adapter.decode(&call.frame) {
+            Ok(fields) => fields,
+            Err(err) if fatal_decode_errors => {
+                return Err(err.context(format!(
+                    "failed to decode transaction {} for call {}",
+                    call.frame.transaction_hash,
+                    adapter.name()
+                )));
+            }
+            Err(err) => {
+                tracing::warn!(?err, frame = ?call.frame, "failed to decode call");
+                continue;
+            }
+        };
+
+        let address = call.frame.to.unwrap_or_default();
+        logs.push(database::Log {
+            event: adapter.name(),
+            block_number: call.header.number.as_u64(),
+            block_timestamp: call.header.timestamp.as_u64(),
+            block_hash: call.header.hash,
+            log_index: call.frame.position,
+            transaction_index: call.frame.transaction_index,
+            transaction_hash: call.frame.transaction_hash,
+            event_ordinal: index,
+            address,
+            contract_label: adapter.contract_label(&address),
+            fields,
+        });
+    }
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solabi::ethprim::address};
+
+    fn frame(to: Address, input: Vec<u8>) -> Frame {
+        Frame {
+            to: Some(to),
+            input,
+            success: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_selector_and_contract() {
+        let settlement = address!("0x9008D19f58AAbD9eD0D60971565AA8510560ab41");
+        let mut config =
+            config::Call::for_signature("function transfer(address to, uint256 value)");
+        config.contract = config::Contract::Address(settlement);
+        let adapter = CallAdapter::new(config.clone()).unwrap();
+        assert!(!adapter.traced());
+
+        let input = hex_literal::hex!("a9059cbb").to_vec();
+        assert!(adapter.matches(&frame(settlement, input.clone())));
+        assert!(!adapter.matches(&frame(Address::default(), input.clone())));
+        assert!(!adapter.matches(&frame(settlement, vec![0xa9, 0x05])));
+        assert!(!adapter.matches(&Frame {
+            to: None,
+            ..frame(settlement, input.clone())
+        }));
+
+        // Internal calls are only matched when configured.
+        let internal = Frame {
+            depth: 1,
+            ..frame(settlement, input)
+        };
+        assert!(!adapter.matches(&internal));
+        config.internal = true;
+        let adapter = CallAdapter::new(config).unwrap();
+        assert!(adapter.traced());
+        assert!(adapter.matches(&internal));
+    }
+
+    #[test]
+    fn matches_transfers() {
+        let settlement = address!("0x9008D19f58AAbD9eD0D60971565AA8510560ab41");
+        let adapter = CallAdapter::transfer(config::Transfer {
+            name: "transfers".to_string(),
+            start: 0,
+            end: None,
+            contract: config::Contract::Address(settlement),
+        })
+        .unwrap();
+        assert!(adapter.traced());
+
+        let transfer = Frame {
+            from: settlement,
+            value: U256::from(1_u64),
+            ..frame(Address([1; 20]), Vec::new())
+        };
+        assert!(adapter.matches(&transfer));
+        assert!(adapter.matches(&Frame {
+            from: Address([1; 20]),
+            to: Some(settlement),
+            ..transfer.clone()
+        }));
+        assert!(!adapter.matches(&Frame {
+            value: U256::ZERO,
+            ..transfer.clone()
+        }));
+        assert!(!adapter.matches(&Frame {
+            success: false,
+            ..transfer.clone()
+        }));
+        assert!(!adapter.matches(&Frame {
+            from: Address([1; 20]),
+            ..transfer
+        }));
+    }
+
+    #[test]
+    fn decodes_calls() {
+        let adapter = CallAdapter::for_signature("function transfer(address to, uint256 value)");
+        assert_eq!(
+            adapter
+                .signature()
+                .inputs
+                .iter()
+                .map(|input| input.field.name.as_str())
+                .collect::<Vec<_>>(),
+            ["sender", "value", "success", "to", "value"]
+        );
+
+        let mut input = hex_literal::hex!("a9059cbb").to_vec();
+        input.extend([0; 12]);
+        input.extend([1; 20]);
+        input.extend(U256::from(42_u64).to_be_bytes());
+        let sender = Address([2; 20]);
+        let fields = adapter
+            .decode(&Frame {
+                from: sender,
+                success: false,
+                ..frame(Address::default(), input)
+            })
+            .unwrap();
+        assert_eq!(
+            fields,
+            [
+                Value::Address(sender),
+                Value::Uint(Uint::new(256, U256::ZERO).unwrap()),
+                Value::Bool(false),
+                Value::Address(Address([1; 20])),
+                Value::Uint(Uint::new(256, U256::from(42_u64)).unwrap()),
+            ]
+        );
+
+        assert!(adapter
+            .decode(&frame(Address::default(), vec![0xa9, 0x05, 0x9c, 0xbb]))
+            .is_err());
+    }
+}
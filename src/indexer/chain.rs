@@ -27,6 +27,26 @@ impl Chain {
         self.finalized + self.hashes.len().as_u256()
     }
 
+    /// Returns the hash of the latest block in the chain.
+    pub fn head(&self) -> Digest {
+        self.hashes[0]
+    }
+
+    /// Returns the finalized block number.
+    pub fn finalized(&self) -> U256 {
+        self.finalized
+    }
+
+    /// Returns the hash of a block in the chain, or `None` if the block is
+    /// before the finalized block or not in the chain yet.
+    pub fn hash(&self, number: U256) -> Option<Digest> {
+        if number < self.finalized || number >= self.next() {
+            return None;
+        }
+        let index = (self.next() - number - 1).as_usize();
+        Some(self.hashes[index])
+    }
+
     /// Appends the next block in the chain to the local state.
     pub fn append(&mut self, hash: Digest, parent: Digest) -> Result<Append> {
         if parent != self.hashes[0] {
@@ -92,6 +112,11 @@ mod tests {
 
         assert_eq!(chain.append(d(0x40), d(0x31)).unwrap(), Append::Ok);
         assert_eq!(chain.next(), 5);
+
+        assert_eq!(chain.hash(U256::new(0)), None);
+        assert_eq!(chain.hash(U256::new(1)), Some(d(0x10)));
+        assert_eq!(chain.hash(U256::new(3)), Some(d(0x31)));
+        assert_eq!(chain.hash(U256::new(5)), None);
     }
 
     #[test]
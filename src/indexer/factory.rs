@@ -0,0 +1,206 @@
+//! Discovery of contracts from factory events. Events can be configured to be
+//! indexed for all contracts whose addresses are emitted in an address field of
+//! another indexed event, for example the pools created by a factory contract.
+
+use {
+    super::adapter::Adapter,
+    anyhow::{ensure, Context, Result},
+    ethrpc::types::Log,
+    solabi::{
+        ethprim::Address,
+        value::{Value, ValueKind},
+    },
+    std::collections::HashSet,
+};
+
+/// Links an adapter for events of factory-discovered contracts to the adapter
+/// of the factory event.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Link {
+    /// The index of the adapter for the discovered contracts' events.
+    pub child: usize,
+    /// The index of the factory event adapter.
+    pub factory: usize,
+    /// The index of the factory event field containing the contract address.
+    pub field: usize,
+}
+
+/// A contract discovered from a factory event.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Child {
+    /// The index of the adapter that the contract was discovered for.
+    pub adapter: usize,
+    pub address: Address,
+    /// The block that the factory event was emitted in.
+    pub block: u64,
+}
+
+impl Link {
+    /// Resolves the factory configurations of all adapters. Factory events
+    /// must be indexed themselves and can't be for factory-discovered
+    /// contracts.
+    pub fn resolve(adapters: &[Adapter]) -> Result<Vec<Self>> {
+        let mut links = Vec::new();
+        for (child, adapter) in adapters.iter().enumerate() {
+            let Some(config) = adapter.factory() else {
+                continue;
+            };
+            let factory = adapters
+                .iter()
+                .position(|adapter| adapter.name() == config.from_event)
+                .with_context(|| {
+                    format!(
+                        "event {} uses unknown factory event {}",
+                        adapter.name(),
+                        config.from_event
+                    )
+                })?;
+            ensure!(
+                adapters[factory].factory().is_none(),
+                "factory event {} is itself for factory-discovered contracts",
+                config.from_event
+            );
+            let field = adapters[factory]
+                .signature()
+                .inputs
+                .iter()
+                .position(|input| input.field.name == config.field)
+                .with_context(|| {
+                    format!(
+                        "factory event {} has no field {}",
+                        config.from_event, config.field
+                    )
+                })?;
+            ensure!(
+                adapters[factory].signature().inputs[field].field.kind == ValueKind::Address,
+                "factory event field {}.{} is not an address",
+                config.from_event,
+                config.field
+            );
+            links.push(Self {
+                child,
+                factory,
+                field,
+            });
+        }
+        Ok(links)
+    }
+}
+
+/// Discovers new contracts from factory event logs. `logs` contains the logs
+/// for each adapter. Contracts that are already `known` to their adapter are
+/// skipped.
+pub fn discover(
+    adapters: &[Adapter],
+    links: &[Link],
+    logs: &[Vec<Log>],
+    known: impl Fn(&Adapter, &Address) -> bool,
+) -> Vec<Child> {
+    let mut seen = HashSet::new();
+    let mut children = Vec::new();
+    for link in links {
+        for log in &logs[link.factory] {
+            let fields = match adapters[link.factory].decode(&log.topics, &log.data) {
+                Ok(fields) => fields,
+                Err(err) => {
+                    tracing::warn!(?err, ?log, "failed to decode factory log");
+                    continue;
+                }
+            };
+            let Some(Value::Address(address)) = fields.get(link.field) else {
+                continue;
+            };
+            if known(&adapters[link.child], address) || !seen.insert((link.child, *address)) {
+                continue;
+            }
+            tracing::debug!(
+                event = %adapters[link.child].name(), %address,
+                "discovered contract"
+            );
+            children.push(Child {
+                adapter: link.child,
+                address: *address,
+                block: log.block_number.as_u64(),
+            });
+        }
+    }
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{config, indexer::hex},
+        serde_json::json,
+        solabi::ethprim::{address, keccak},
+    };
+
+    fn adapters(from_event: &str, field: &str) -> Vec<Adapter> {
+        let mut pool = config::Event::for_signature("event Swap()");
+        pool.contract = config::Contract::Factory(config::Factory {
+            from_event: from_event.to_string(),
+            field: field.to_string(),
+        });
+        vec![
+            Adapter::for_signature("event PoolCreated(address indexed token, address pool)"),
+            Adapter::new(pool).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn resolves_links() {
+        assert_eq!(
+            Link::resolve(&adapters("PoolCreated", "pool")).unwrap(),
+            [Link {
+                child: 1,
+                factory: 0,
+                field: 1,
+            }]
+        );
+        assert!(Link::resolve(&adapters("Unknown", "pool")).is_err());
+        assert!(Link::resolve(&adapters("PoolCreated", "unknown")).is_err());
+        assert!(Link::resolve(&adapters("Swap", "pool")).is_err());
+    }
+
+    #[test]
+    fn discovers_new_contracts() {
+        let mut adapters = adapters("PoolCreated", "pool");
+        let links = Link::resolve(&adapters).unwrap();
+        let known = address!("0x0101010101010101010101010101010101010101");
+        let new = address!("0x0202020202020202020202020202020202020202");
+        adapters[1].add_child(known, 1);
+
+        let log = |block: u64, pool: Address| -> Log {
+            let mut data = [0; 32];
+            data[12..].copy_from_slice(&pool.0);
+            serde_json::from_value(json!({
+                "removed": false,
+                "logIndex": "0x0",
+                "transactionIndex": "0x0",
+                "transactionHash": hex::encode(&[0; 32]),
+                "blockHash": hex::encode(&[0; 32]),
+                "blockNumber": format!("{block:#x}"),
+                "address": hex::encode(&[0; 20]),
+                "data": hex::encode(&data),
+                "topics": [
+                    hex::encode(&keccak!("PoolCreated(address,address)").0),
+                    hex::encode(&[0; 32]),
+                ],
+            }))
+            .unwrap()
+        };
+        let logs = vec![vec![log(2, known), log(3, new), log(4, new)], vec![]];
+
+        assert_eq!(
+            discover(&adapters, &links, &logs, |adapter, address| {
+                adapter.child(address).is_some()
+            }),
+            [Child {
+                adapter: 1,
+                address: new,
+                block: 3,
+            }]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Failover between multiple Ethereum node endpoints. Requests are sent to the
+//! current endpoint and move on to the next endpoint, in order of priority,
+//! when it fails with a transient error. After a cooldown, endpoints with a
+//! higher priority than the current one are tried again.
+
+use {
+    super::{
+        node::{self, BlockId, Frame, Header, Node, Receipt, Tracer, Transaction, ViewCall},
+        retry,
+    },
+    anyhow::Result,
+    ethrpc::types::{Digest, Log, LogFilter},
+    futures::{
+        future::{self, BoxFuture},
+        FutureExt,
+    },
+    solabi::ethprim::U256,
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// How long to stay on a lower priority endpoint before trying the endpoints
+/// with a higher priority again.
+const COOLDOWN: Duration = Duration::from_secs(300);
+
+/// A node that fails over between multiple endpoints.
+pub struct Failover<N> {
+    /// The endpoints, ordered by priority.
+    nodes: Vec<N>,
+    /// The index of the endpoint currently in use.
+    current: AtomicUsize,
+    /// When endpoints with a higher priority than the current one were last
+    /// tried.
+    tried: Mutex<Instant>,
+    cooldown: Duration,
+}
+
+impl<N> Failover<N>
+where
+    N: Node,
+{
+    /// Creates a new failover node from endpoints ordered by priority.
+    pub fn new(nodes: Vec<N>) -> Self {
+        assert!(!nodes.is_empty(), "failover without endpoints");
+        Self {
+            nodes,
+            current: AtomicUsize::new(0),
+            tried: Mutex::new(Instant::now()),
+            cooldown: COOLDOWN,
+        }
+    }
+
+    /// Returns the index of the endpoint currently in use.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Switches to the endpoint at the specified index.
+    fn switch(&self, index: usize) {
+        let previous = self.current.swap(index, Ordering::Relaxed);
+        if previous != index {
+            tracing::info!(from = %previous, to = %index, "switched node endpoint");
+            *self.tried.lock().unwrap() = Instant::now();
+        }
+    }
+
+    /// Returns the index of the endpoint to start a request with. This is the
+    /// current endpoint, unless the cooldown has passed since endpoints with a
+    /// higher priority were last tried, in which case we start over with the
+    /// endpoint with the highest priority.
+    fn start(&self) -> usize {
+        let current = self.current();
+        if current == 0 {
+            return current;
+        }
+        let mut tried = self.tried.lock().unwrap();
+        if tried.elapsed() < self.cooldown {
+            return current;
+        }
+        *tried = Instant::now();
+        tracing::debug!(current = %current, "retrying higher priority node endpoints");
+        0
+    }
+
+    /// Executes a request, starting with the current endpoint and trying the
+    /// remaining endpoints in turn on transient errors.
+    ///
+    /// Log range errors are returned right away, since other endpoints are
+    /// likely to reject the query as well and the caller is expected to
+    /// split it instead.
+    async fn request<'a, T>(
+        &'a self,
+        request: impl Fn(&'a N) -> BoxFuture<'a, Result<T>>,
+    ) -> Result<T> {
+        let start = self.start();
+        let mut last = None;
+        for offset in 0..self.nodes.len() {
+            let index = (start + offset) % self.nodes.len();
+            match request(&self.nodes[index]).await {
+                Ok(result) => {
+                    self.switch(index);
+                    return Ok(result);
+                }
+                Err(err) if retry::is_transient(&err) && !node::is_log_range_error(&err) => {
+                    tracing::warn!(?err, endpoint = %index, "node endpoint failed");
+                    last = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last.expect("failover without endpoints"))
+    }
+}
+
+impl<N> Node for Failover<N>
+where
+    N: Node,
+{
+    fn headers<'a>(&'a self, blocks: &'a [BlockId]) -> BoxFuture<'a, Result<Vec<Option<Header>>>> {
+        self.request(move |node| node.headers(blocks)).boxed()
+    }
+
+    fn logs(&self, filters: Vec<LogFilter>) -> BoxFuture<'_, Result<Vec<Vec<Log>>>> {
+        self.request(move |node| node.logs(filters.clone())).boxed()
+    }
+
+    fn transactions<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Transaction>>>>> {
+        self.request(move |node| node.transactions(blocks)).boxed()
+    }
+
+    fn receipts<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Receipt>>>>> {
+        self.request(move |node| node.receipts(blocks)).boxed()
+    }
+
+    fn traces<'a>(
+        &'a self,
+        tracer: Tracer,
+        blocks: &'a [Header],
+    ) -> BoxFuture<'a, Result<Vec<Vec<Frame>>>> {
+        self.request(move |node| node.traces(tracer, blocks))
+            .boxed()
+    }
+
+    fn view_calls<'a>(
+        &'a self,
+        calls: &'a [ViewCall],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<u8>>>>> {
+        self.request(move |node| node.view_calls(calls)).boxed()
+    }
+
+    fn consensus(&self, number: U256) -> BoxFuture<'_, Result<Option<Header>>> {
+        async move {
+            let blocks = [BlockId::Number(number)];
+            let results =
+                future::join_all(self.nodes.iter().map(|node| node.headers(&blocks))).await;
+
+            // Count the votes for each block hash. Endpoints that fail or
+            // don't know the block yet don't get a vote.
+            let mut headers = Vec::new();
+            let mut votes = HashMap::<Digest, usize>::new();
+            let mut errors = Vec::new();
+            for (index, result) in results.into_iter().enumerate() {
+                match result.map(|headers| headers.into_iter().next().flatten()) {
+                    Ok(Some(header)) => {
+                        *votes.entry(header.hash).or_default() += 1;
+                        headers.push((index, header));
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::warn!(?err, endpoint = %index, "node endpoint failed");
+                        errors.push(err);
+                    }
+                }
+            }
+            if errors.len() == self.nodes.len() {
+                return Err(errors.pop().expect("failover without endpoints"));
+            }
+            if headers.is_empty() {
+                return Ok(None);
+            }
+
+            // Ties are resolved in favour of the endpoint with the highest
+            // priority, which is also the one we continue with.
+            let max = votes.values().copied().max().unwrap_or_default();
+            let (index, header) = headers
+                .into_iter()
+                .find(|(_, header)| votes[&header.hash] == max)
+                .expect("missing header with most votes");
+            tracing::debug!(
+                block = %number, hash = %header.hash, votes = %max,
+                "node endpoint consensus"
+            );
+
+            self.switch(index);
+            Ok(Some(header))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::indexer::fake::{self, FakeLog},
+        ethrpc::types::{ArrayVec, LogBlocks, LogFilterValue},
+        solabi::ethprim::{keccak, Address},
+    };
+
+    async fn header(failover: &Failover<fake::Node>, number: u64) -> Result<Option<Header>> {
+        let blocks = [BlockId::Number(U256::from(number))];
+        Ok(failover
+            .headers(&blocks)
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_transient_errors() {
+        let failover = Failover::new(vec![fake::Node::new(), fake::Node::new()]);
+        let block = failover.nodes[1].mine(vec![]);
+
+        failover.nodes[0].fail(true);
+        assert_eq!(header(&failover, 1).await.unwrap(), Some(block));
+        assert_eq!(failover.current(), 1);
+
+        // Stick with the new endpoint while it works.
+        failover.nodes[0].fail(false);
+        assert_eq!(header(&failover, 1).await.unwrap(), Some(block));
+        assert_eq!(failover.current(), 1);
+
+        failover.nodes[1].fail(true);
+        assert_eq!(header(&failover, 1).await.unwrap(), None);
+        assert_eq!(failover.current(), 0);
+
+        failover.nodes[0].fail(true);
+        assert!(header(&failover, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn retries_higher_priority_endpoints_after_cooldown() {
+        let mut failover = Failover::new(vec![fake::Node::new(), fake::Node::new()]);
+        failover.nodes[0].fail(true);
+        header(&failover, 1).await.unwrap();
+        assert_eq!(failover.current(), 1);
+
+        failover.cooldown = Duration::ZERO;
+        header(&failover, 1).await.unwrap();
+        assert_eq!(failover.current(), 1);
+
+        failover.nodes[0].fail(false);
+        header(&failover, 1).await.unwrap();
+        assert_eq!(failover.current(), 0);
+    }
+
+    #[tokio::test]
+    async fn does_not_fail_over_on_log_range_errors() {
+        let failover = Failover::new(vec![fake::Node::new(), fake::Node::new()]);
+        for node in &failover.nodes {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        failover.nodes[0].limit_logs(0);
+
+        let filter = LogFilter {
+            address: LogFilterValue::Any,
+            topics: ArrayVec::new(),
+            blocks: LogBlocks::Range {
+                from: 0_u64.into(),
+                to: 1_u64.into(),
+            },
+        };
+        let err = failover.logs(vec![filter]).await.unwrap_err();
+
+        assert!(node::is_log_range_error(&err));
+        assert!(failover.nodes[1].log_queries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn consensus_follows_majority() {
+        let failover = Failover::new(vec![
+            fake::Node::new(),
+            fake::Node::new(),
+            fake::Node::new(),
+        ]);
+        let block = failover
+            .nodes
+            .iter()
+            .map(|node| node.mine(vec![]))
+            .last()
+            .unwrap();
+        failover.nodes[0].reorg(1);
+        let fork = failover.nodes[0].mine(vec![]);
+
+        assert_eq!(failover.consensus(U256::new(1)).await.unwrap(), Some(block));
+        assert_eq!(failover.current(), 1);
+
+        // Ties are won by the endpoint with the highest priority.
+        failover.nodes[2].fail(true);
+        assert_eq!(failover.consensus(U256::new(1)).await.unwrap(), Some(fork));
+        assert_eq!(failover.current(), 0);
+    }
+}
@@ -0,0 +1,443 @@
+//! A fake in-memory Ethereum node for testing the indexer.
+
+use {
+    super::{
+        hex,
+        node::{self, BlockId, Frame, Header, Receipt, Tracer, Transaction, ViewCall},
+    },
+    anyhow::{anyhow, Context, Result},
+    ethrpc::types::{Log, LogFilter},
+    futures::{future::BoxFuture, FutureExt},
+    serde_json::{json, Value as Json},
+    solabi::ethprim::{Address, Digest, U256},
+    std::{io, iter, sync::Mutex},
+};
+
+/// A fake Ethereum node with a scripted blockchain.
+#[derive(Default)]
+pub struct Node {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The canonical chain, indexed by block number.
+    blocks: Vec<Block>,
+    finalized: u64,
+    /// Incremented on every reorg so that replacement blocks get new hashes.
+    fork: u64,
+    /// Whether all requests to the node should fail.
+    failing: bool,
+    /// The maximum number of logs an `eth_getLogs` query may return before it
+    /// gets rejected.
+    max_logs: Option<usize>,
+    /// The block ranges of all `eth_getLogs` queries that were made.
+    log_queries: Vec<(u64, u64)>,
+}
+
+struct Block {
+    header: Header,
+    logs: Vec<FakeLog>,
+    transactions: Vec<FakeTransaction>,
+}
+
+/// A log to include in a block.
+#[derive(Clone, Debug, Default)]
+pub struct FakeLog {
+    pub address: Address,
+    pub topics: Vec<Digest>,
+    pub data: Vec<u8>,
+}
+
+/// A transaction to include in a block.
+#[derive(Clone, Debug)]
+pub struct FakeTransaction {
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub input: Vec<u8>,
+    pub success: bool,
+    /// Internal calls made by the transaction, which are only visible in
+    /// traces.
+    pub calls: Vec<FakeTransaction>,
+}
+
+impl Node {
+    /// Creates a new fake node with only a genesis block.
+    pub fn new() -> Self {
+        let node = Self::default();
+        node.mine(vec![]);
+        node
+    }
+
+    /// Mines a new block with the specified logs, each log in its own
+    /// transaction.
+    pub fn mine(&self, logs: Vec<FakeLog>) -> Header {
+        self.mine_block(logs, vec![])
+    }
+
+    /// Mines a new block with the specified transactions and no logs.
+    pub fn mine_transactions(&self, transactions: Vec<FakeTransaction>) -> Header {
+        self.mine_block(vec![], transactions)
+    }
+
+    fn mine_block(&self, logs: Vec<FakeLog>, transactions: Vec<FakeTransaction>) -> Header {
+        let mut state = self.state.lock().unwrap();
+        let number = state.blocks.len() as u64;
+        let header = Header {
+            number: U256::from(number),
+            hash: hash(number, state.fork),
+            parent_hash: state
+                .blocks
+                .last()
+                .map(|block| block.header.hash)
+                .unwrap_or_default(),
+            timestamp: U256::from(1_600_000_000 + number * 12),
+        };
+        state.blocks.push(Block {
+            header,
+            logs,
+            transactions,
+        });
+        header
+    }
+
+    /// Sets the finalized block.
+    pub fn finalize(&self, number: u64) {
+        self.state.lock().unwrap().finalized = number;
+    }
+
+    /// Drops all blocks starting at the specified block number, so that new
+    /// blocks mined afterwards replace them.
+    pub fn reorg(&self, number: u64) {
+        let mut state = self.state.lock().unwrap();
+        assert!(number > state.finalized, "reorg of finalized block");
+        state.blocks.truncate(number as usize);
+        state.fork += 1;
+    }
+
+    /// Makes all requests to the node fail with a connection error.
+    pub fn fail(&self, failing: bool) {
+        self.state.lock().unwrap().failing = failing;
+    }
+
+    /// Rejects `eth_getLogs` queries that would return more than `max` logs.
+    pub fn limit_logs(&self, max: usize) {
+        self.state.lock().unwrap().max_logs = Some(max);
+    }
+
+    /// Returns the block ranges of all `eth_getLogs` queries that were made.
+    pub fn log_queries(&self) -> Vec<(u64, u64)> {
+        self.state.lock().unwrap().log_queries.clone()
+    }
+}
+
+impl node::Node for Node {
+    fn headers<'a>(&'a self, blocks: &'a [BlockId]) -> BoxFuture<'a, Result<Vec<Option<Header>>>> {
+        async move {
+            let state = self.state.lock().unwrap();
+            state.check()?;
+            Ok(blocks
+                .iter()
+                .map(|block| {
+                    let number = match block {
+                        BlockId::Number(number) => number.as_usize(),
+                        BlockId::Latest => state.blocks.len() - 1,
+                        BlockId::Safe | BlockId::Finalized => state.finalized as usize,
+                    };
+                    state.blocks.get(number).map(|block| block.header)
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn logs(&self, filters: Vec<LogFilter>) -> BoxFuture<'_, Result<Vec<Vec<Log>>>> {
+        async move {
+            let mut state = self.state.lock().unwrap();
+            state.check()?;
+            filters.iter().map(|filter| state.logs(filter)).collect()
+        }
+        .boxed()
+    }
+
+    fn transactions<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Transaction>>>>> {
+        async move {
+            let state = self.state.lock().unwrap();
+            state.check()?;
+            Ok(blocks
+                .iter()
+                .map(|hash| {
+                    let block = state.block(hash)?;
+                    let number = block.header.number.as_u64();
+                    Some(
+                        block
+                            .transactions
+                            .iter()
+                            .enumerate()
+                            .map(|(index, transaction)| Transaction {
+                                hash: transaction_hash(number, index as u64),
+                                index: index as u64,
+                                from: transaction.from,
+                                to: transaction.to,
+                                value: transaction.value,
+                                input: transaction.input.clone(),
+                            })
+                            .collect(),
+                    )
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn receipts<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Receipt>>>>> {
+        async move {
+            let state = self.state.lock().unwrap();
+            state.check()?;
+            Ok(blocks
+                .iter()
+                .map(|hash| {
+                    let block = state.block(hash)?;
+                    let number = block.header.number.as_u64();
+                    // Blocks either have transactions or logs, each log in its
+                    // own successful transaction.
+                    let transactions = block
+                        .transactions
+                        .iter()
+                        .map(|transaction| (transaction.from, transaction.to, transaction.success))
+                        .chain(
+                            block
+                                .logs
+                                .iter()
+                                .map(|log| (Address::default(), Some(log.address), true)),
+                        );
+                    Some(
+                        transactions
+                            .enumerate()
+                            .map(|(index, (from, to, success))| Receipt {
+                                transaction_hash: transaction_hash(number, index as u64),
+                                transaction_index: index as u64,
+                                from: Some(from),
+                                to,
+                                gas_used: Some(21_000 + index as u64),
+                                effective_gas_price: Some(1_000_000_000),
+                                success: Some(success),
+                            })
+                            .collect(),
+                    )
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn traces<'a>(
+        &'a self,
+        _: Tracer,
+        blocks: &'a [Header],
+    ) -> BoxFuture<'a, Result<Vec<Vec<Frame>>>> {
+        async move {
+            let state = self.state.lock().unwrap();
+            state.check()?;
+            blocks
+                .iter()
+                .map(|header| {
+                    let block = state.block(&header.hash).context("unknown block")?;
+                    let number = header.number.as_u64();
+                    let mut frames = Vec::new();
+                    for (index, transaction) in block.transactions.iter().enumerate() {
+                        let calls = iter::once((0, transaction, transaction.success)).chain(
+                            transaction
+                                .calls
+                                .iter()
+                                .map(|call| (1, call, transaction.success && call.success)),
+                        );
+                        for (depth, call, success) in calls {
+                            frames.push(Frame {
+                                transaction_hash: transaction_hash(number, index as u64),
+                                transaction_index: index as u64,
+                                position: frames.len() as u64,
+                                depth,
+                                from: call.from,
+                                to: call.to,
+                                value: call.value,
+                                input: call.input.clone(),
+                                success,
+                            });
+                        }
+                    }
+                    Ok(frames)
+                })
+                .collect()
+        }
+        .boxed()
+    }
+
+    /// View calls return the block number as a single word, except for calls
+    /// to the zero address, which revert.
+    fn view_calls<'a>(
+        &'a self,
+        calls: &'a [ViewCall],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<u8>>>>> {
+        async move {
+            let state = self.state.lock().unwrap();
+            state.check()?;
+            calls
+                .iter()
+                .map(|call| {
+                    let block = state.block(&call.block).context("unknown block")?;
+                    Ok((call.to != Address::default())
+                        .then(|| block.header.number.to_be_bytes().to_vec()))
+                })
+                .collect()
+        }
+        .boxed()
+    }
+}
+
+impl State {
+    fn check(&self) -> Result<()> {
+        if self.failing {
+            return Err(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        }
+        Ok(())
+    }
+
+    fn block(&self, hash: &Digest) -> Option<&Block> {
+        self.blocks.iter().find(|block| block.header.hash == *hash)
+    }
+
+    fn logs(&mut self, filter: &LogFilter) -> Result<Vec<Log>> {
+        // Inspect the filter in its JSON RPC representation, this way we don't
+        // need to care about how `ethrpc` represents them.
+        let filter = serde_json::to_value(filter)?;
+        let (from, to) = match filter.get("blockHash") {
+            Some(hash) if !hash.is_null() => {
+                let block = self
+                    .blocks
+                    .iter()
+                    .find(|block| matches(hash, &hex::encode(&block.header.hash.0)))
+                    .context("unknown block")?;
+                let number = block.header.number.as_u64();
+                (number, number)
+            }
+            _ => (
+                quantity(&filter["fromBlock"])?,
+                quantity(&filter["toBlock"])?,
+            ),
+        };
+        self.log_queries.push((from, to));
+
+        let mut logs = Vec::new();
+        for number in from..=to {
+            let Some(block) = self.blocks.get(number as usize) else {
+                break;
+            };
+            for (index, log) in block.logs.iter().enumerate() {
+                if !matches(&filter["address"], &hex::encode(&log.address.0)) {
+                    continue;
+                }
+                let topics = filter["topics"].as_array().cloned().unwrap_or_default();
+                if topics.len() > log.topics.len()
+                    || !topics
+                        .iter()
+                        .zip(&log.topics)
+                        .all(|(filter, topic)| matches(filter, &hex::encode(&topic.0)))
+                {
+                    continue;
+                }
+
+                logs.push(serde_json::from_value(json!({
+                    "removed": false,
+                    "logIndex": format!("{index:#x}"),
+                    "transactionIndex": format!("{index:#x}"),
+                    "transactionHash": hex::encode(&transaction_hash(number, index as u64).0),
+                    "blockHash": hex::encode(&block.header.hash.0),
+                    "blockNumber": format!("{number:#x}"),
+                    "address": hex::encode(&log.address.0),
+                    "data": hex::encode(&log.data),
+                    "topics": log.topics.iter().map(|topic| hex::encode(&topic.0)).collect::<Vec<_>>(),
+                }))?);
+            }
+        }
+
+        if let Some(max) = self.max_logs {
+            if logs.len() > max {
+                return Err(anyhow!("query returned more than {max} results"));
+            }
+        }
+        Ok(logs)
+    }
+}
+
+impl FakeTransaction {
+    /// Creates a successful transaction calling a contract with the specified
+    /// input and no value.
+    pub fn call(from: Address, to: Address, input: Vec<u8>) -> Self {
+        Self {
+            from,
+            to: Some(to),
+            value: U256::ZERO,
+            input,
+            success: true,
+            calls: Vec::new(),
+        }
+    }
+}
+
+impl FakeLog {
+    /// Creates a log for the specified contract and topics without data.
+    pub fn new(address: Address, topics: &[Digest]) -> Self {
+        Self {
+            address,
+            topics: topics.to_vec(),
+            data: Vec::new(),
+        }
+    }
+}
+
+/// Computes a fake hash for a block number and fork.
+fn hash(number: u64, fork: u64) -> Digest {
+    let mut hash = [0; 32];
+    hash[..8].copy_from_slice(&number.to_be_bytes());
+    hash[8..16].copy_from_slice(&fork.to_be_bytes());
+    hash[31] = 1;
+    Digest(hash)
+}
+
+/// Computes a fake transaction hash for a transaction index in a block.
+fn transaction_hash(number: u64, index: u64) -> Digest {
+    let mut hash = [0; 32];
+    hash[..8].copy_from_slice(&number.to_be_bytes());
+    hash[8..16].copy_from_slice(&index.to_be_bytes());
+    hash[31] = 2;
+    Digest(hash)
+}
+
+fn quantity(value: &Json) -> Result<u64> {
+    let value = value.as_str().context("expected block number")?;
+    let value = value.strip_prefix("0x").context("expected hex quantity")?;
+    Ok(u64::from_str_radix(value, 16)?)
+}
+
+/// Checks whether a JSON RPC log filter value matches the specified value.
+fn matches(filter: &Json, value: &str) -> bool {
+    let exact = |filter: &Json| {
+        filter
+            .as_str()
+            .map(|filter| filter.eq_ignore_ascii_case(value))
+            .unwrap_or_default()
+    };
+    match filter {
+        Json::Null => true,
+        Json::Array(filters) => filters.iter().any(exact),
+        filter => exact(filter),
+    }
+}
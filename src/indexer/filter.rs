@@ -0,0 +1,292 @@
+//! Typed topic filters. Filters are configured by indexed event field name and
+//! encoded into topics the same way that the EVM computes them for the field's
+//! type, including hashing of dynamic types.
+//!
+//! The same TOML value formats are used for the arguments of snapshot calls.
+
+use {
+    super::hex,
+    anyhow::{bail, ensure, Context, Result},
+    ethrpc::types::LogFilterValue,
+    solabi::{
+        abi::EventDescriptor,
+        ethprim::{Address, Digest, U256},
+        value::{FixedBytes, Int, Uint, Value, ValueKind},
+    },
+    std::collections::BTreeMap,
+    toml::Value as Toml,
+};
+
+/// Computes the topic filters for the indexed fields of an event. This does
+/// not include the event selector.
+pub fn topics(
+    signature: &EventDescriptor,
+    filter: &BTreeMap<String, Toml>,
+) -> Result<Vec<LogFilterValue<Digest>>> {
+    for name in filter.keys() {
+        let input = signature
+            .inputs
+            .iter()
+            .find(|input| input.field.name == *name)
+            .with_context(|| format!("filter for unknown event field '{name}'"))?;
+        ensure!(input.indexed, "filter for non-indexed event field '{name}'");
+    }
+
+    let mut topics = signature
+        .inputs
+        .iter()
+        .filter(|input| input.indexed)
+        .map(|input| {
+            let kind = &input.field.kind;
+            let topic = match filter.get(&input.field.name) {
+                None => LogFilterValue::Any,
+                Some(Toml::Array(values)) => LogFilterValue::OneOf(
+                    values
+                        .iter()
+                        .map(|value| encode(kind, value))
+                        .collect::<Result<_>>()?,
+                ),
+                Some(value) => LogFilterValue::Exact(encode(kind, value)?),
+            };
+            Ok(topic)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Trailing wildcards are implied.
+    while matches!(topics.last(), Some(LogFilterValue::Any)) {
+        topics.pop();
+    }
+    Ok(topics)
+}
+
+/// Encodes a filter value into a topic for an indexed field of the specified
+/// type.
+fn encode(kind: &ValueKind, value: &Toml) -> Result<Digest> {
+    let topic = match (kind, value) {
+        (ValueKind::Address, Toml::String(value)) => {
+            let address = value.parse::<Address>()?;
+            let mut topic = Digest::default();
+            topic.0[12..].copy_from_slice(&address.0);
+            topic
+        }
+        (ValueKind::Bool, Toml::Boolean(value)) => word(U256::from(*value as u8)),
+        (ValueKind::Uint(bits), value) => {
+            let (negative, value) = integer(value)?;
+            ensure!(
+                !negative && (*bits == 256 || value >> *bits as u32 == 0),
+                "value out of range for uint{bits}"
+            );
+            word(value)
+        }
+        (ValueKind::Int(bits), value) => {
+            let (negative, value) = integer(value)?;
+            let limit = U256::ONE << (*bits - 1) as u32;
+            ensure!(
+                if negative {
+                    value <= limit
+                } else {
+                    value < limit
+                },
+                "value out of range for int{bits}"
+            );
+            word(if negative {
+                value.wrapping_neg()
+            } else {
+                value
+            })
+        }
+        (ValueKind::FixedBytes(len), Toml::String(value)) => {
+            let bytes = hex::decode(value)?;
+            ensure!(bytes.len() == *len, "expected {len} bytes");
+            let mut topic = Digest::default();
+            topic.0[..*len].copy_from_slice(&bytes);
+            topic
+        }
+        // Dynamic types are hashed when they are indexed.
+        (ValueKind::String, Toml::String(value)) => Digest::of(value.as_bytes()),
+        (ValueKind::Bytes, Toml::String(value)) => Digest::of(hex::decode(value)?),
+        (kind, value) => bail!("unsupported filter value {value} for type {kind:?}"),
+    };
+    Ok(topic)
+}
+
+/// Converts a TOML value into an ABI value of the specified type. Only
+/// elementary types are supported.
+pub fn value(kind: &ValueKind, value: &Toml) -> Result<Value> {
+    let value = match (kind, value) {
+        (ValueKind::Address, Toml::String(value)) => Value::Address(value.parse()?),
+        (ValueKind::Bool, Toml::Boolean(value)) => Value::Bool(*value),
+        (ValueKind::Uint(bits), value) => {
+            let (negative, value) = integer(value)?;
+            ensure!(!negative, "value out of range for uint{bits}");
+            Value::Uint(Uint::new(*bits, value)?)
+        }
+        (ValueKind::Int(bits), value) => {
+            let (negative, value) = integer(value)?;
+            let value = if negative {
+                value.wrapping_neg()
+            } else {
+                value
+            };
+            Value::Int(Int::new(*bits, value.as_i256())?)
+        }
+        (ValueKind::FixedBytes(len), Toml::String(value)) => {
+            let bytes = hex::decode(value)?;
+            ensure!(bytes.len() == *len, "expected {len} bytes");
+            Value::FixedBytes(FixedBytes::new(&bytes)?)
+        }
+        (ValueKind::String, Toml::String(value)) => Value::String(value.clone()),
+        (ValueKind::Bytes, Toml::String(value)) => Value::Bytes(hex::decode(value)?),
+        (kind, value) => bail!("unsupported value {value} for type {kind:?}"),
+    };
+    Ok(value)
+}
+
+fn word(value: U256) -> Digest {
+    Digest(value.to_be_bytes())
+}
+
+/// Parses an integer filter value, either a TOML integer or a decimal or `0x`
+/// prefixed hexadecimal string for values that don't fit into 64 bits.
+/// Returns the sign and the magnitude.
+fn integer(value: &Toml) -> Result<(bool, U256)> {
+    match value {
+        Toml::Integer(value) => Ok((*value < 0, U256::from(value.unsigned_abs()))),
+        Toml::String(value) => {
+            let (negative, value) = match value.strip_prefix('-') {
+                Some(value) => (true, value),
+                None => (false, value.as_str()),
+            };
+            let magnitude = match value.strip_prefix("0x") {
+                Some(hex) => U256::from_str_radix(hex, 16)?,
+                None => U256::from_str_radix(value, 10)?,
+            };
+            Ok((negative, magnitude))
+        }
+        _ => bail!("expected integer filter value"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solabi::ethprim::{address, digest, keccak, I256},
+    };
+
+    fn filter(toml: &str) -> BTreeMap<String, Toml> {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn exact(topics: Vec<LogFilterValue<Digest>>) -> Vec<Option<Digest>> {
+        topics
+            .into_iter()
+            .map(|topic| match topic {
+                LogFilterValue::Any => None,
+                LogFilterValue::Exact(topic) => Some(topic),
+                LogFilterValue::OneOf(_) => panic!("unexpected topic list"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encodes_topics() {
+        let signature = EventDescriptor::parse_declaration(
+            "event Foo(address indexed from, address indexed to, uint256 value)",
+        )
+        .unwrap();
+        let topics = topics(
+            &signature,
+            &filter(r#"to = "0x9008d19f58aabd9ed0d60971565aa8510560ab41""#),
+        )
+        .unwrap();
+
+        assert_eq!(
+            exact(topics),
+            [
+                None,
+                Some(digest!(
+                    "0x0000000000000000000000009008d19f58aabd9ed0d60971565aa8510560ab41"
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_topic_types() {
+        let signature = EventDescriptor::parse_declaration(
+            "event Foo(int8 indexed a, bool indexed b, string indexed c, bytes2 c2) anonymous;",
+        )
+        .unwrap();
+        let topics = topics(&signature, &filter(r#"a = -1, b = true, c = "hello""#)).unwrap();
+
+        assert_eq!(
+            exact(topics),
+            [
+                Some(Digest([0xff; 32])),
+                Some(digest!(
+                    "0x0000000000000000000000000000000000000000000000000000000000000001"
+                )),
+                Some(keccak!("hello")),
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_topic_lists() {
+        let signature =
+            EventDescriptor::parse_declaration("event Foo(uint256 indexed id)").unwrap();
+        let topics = topics(&signature, &filter(r#"id = [1, "0x2"]"#)).unwrap();
+
+        assert!(matches!(
+            &topics[..],
+            [LogFilterValue::OneOf(ids)] if ids == &[
+                digest!("0x0000000000000000000000000000000000000000000000000000000000000001"),
+                digest!("0x0000000000000000000000000000000000000000000000000000000000000002"),
+            ]
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        let signature = EventDescriptor::parse_declaration(
+            "event Foo(uint8 indexed id, address indexed owner, uint256 value)",
+        )
+        .unwrap();
+        for invalid in [
+            "value = 1",
+            "unknown = 1",
+            "id = 256",
+            "id = -1",
+            "owner = true",
+            r#"owner = "0x01""#,
+        ] {
+            assert!(topics(&signature, &filter(invalid)).is_err());
+        }
+    }
+
+    #[test]
+    fn converts_values() {
+        let values = filter(
+            r#"
+            owner = "0x9008D19f58AAbD9eD0D60971565AA8510560ab41"
+            amount = "0x10"
+            delta = -1
+            "#,
+        );
+        assert_eq!(
+            value(&ValueKind::Address, &values["owner"]).unwrap(),
+            Value::Address(address!("0x9008D19f58AAbD9eD0D60971565AA8510560ab41"))
+        );
+        assert_eq!(
+            value(&ValueKind::Uint(256), &values["amount"]).unwrap(),
+            Value::Uint(Uint::new(256, U256::from(16_u64)).unwrap())
+        );
+        assert_eq!(
+            value(&ValueKind::Int(8), &values["delta"]).unwrap(),
+            Value::Int(Int::new(8, I256::new(-1)).unwrap())
+        );
+        assert!(value(&ValueKind::Uint(8), &values["delta"]).is_err());
+        assert!(value(&ValueKind::Bool, &values["amount"]).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! Subscription to new block headers over a WebSocket connection. New heads
+//! are used to wake up the indexer as soon as a new block is available,
+//! instead of polling for it.
+
+use {
+    super::{hex, node::Header},
+    anyhow::{anyhow, bail, Context, Result},
+    futures::{SinkExt, StreamExt},
+    serde_json::{json, Value as Json},
+    solabi::ethprim::{Digest, U256},
+    std::time::Duration,
+    tokio::{
+        net::TcpStream,
+        time::{self, Instant},
+    },
+    tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream},
+    url::Url,
+};
+
+/// How long to poll for before trying to subscribe again after the
+/// subscription failed.
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to wait for connecting and subscribing to new heads.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a new head before checking for new blocks anyway, in
+/// case the subscription silently stopped delivering notifications.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A `newHeads` subscription that falls back to polling while it is not
+/// available.
+pub struct Heads {
+    url: Url,
+    subscription: Option<Subscription>,
+    resubscribe_at: Instant,
+}
+
+impl Heads {
+    /// Creates a new heads subscription for a WebSocket node URL. The
+    /// subscription is established when first waiting for a new head.
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            subscription: None,
+            resubscribe_at: Instant::now(),
+        }
+    }
+
+    /// Waits for a new block. This returns when a new head is received while
+    /// subscribed, or after `poll_interval` has elapsed when the subscription
+    /// is not available. The indexer fetches the new blocks itself, so the
+    /// new head is only logged.
+    pub async fn wait(&mut self, poll_interval: Duration) {
+        if self.subscription.is_none() && Instant::now() >= self.resubscribe_at {
+            match time::timeout(CONNECT_TIMEOUT, Subscription::connect(&self.url)).await {
+                Ok(Ok(subscription)) => {
+                    tracing::info!("subscribed to new heads");
+                    self.subscription = Some(subscription);
+                }
+                Ok(Err(err)) => self.drop_subscription(err),
+                Err(_) => self.drop_subscription(anyhow!("timed out subscribing to new heads")),
+            }
+        }
+
+        let Some(subscription) = &mut self.subscription else {
+            time::sleep(poll_interval).await;
+            return;
+        };
+        match time::timeout(HEAD_TIMEOUT, subscription.next()).await {
+            Ok(Ok(header)) => {
+                tracing::debug!(
+                    block = %header.number, hash = %header.hash,
+                    "received new head"
+                );
+            }
+            Ok(Err(err)) => self.drop_subscription(err),
+            Err(_) => tracing::debug!("timed out waiting for new head"),
+        }
+    }
+
+    /// Drops the current subscription and falls back to polling.
+    fn drop_subscription(&mut self, err: anyhow::Error) {
+        tracing::warn!(
+            ?err,
+            "new heads subscription failed; falling back to polling"
+        );
+        self.subscription = None;
+        self.resubscribe_at = Instant::now() + RESUBSCRIBE_INTERVAL;
+    }
+}
+
+/// An active `newHeads` subscription.
+struct Subscription {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Subscription {
+    /// Connects to the node and subscribes to new heads.
+    async fn connect(url: &Url) -> Result<Self> {
+        let (mut stream, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+        stream
+            .send(Message::Text(
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "eth_subscribe",
+                    "params": ["newHeads"],
+                })
+                .to_string(),
+            ))
+            .await?;
+
+        let mut subscription = Self { stream };
+        let response = subscription.message().await?;
+        if let Some(err) = response.get("error") {
+            bail!("eth_subscribe failed: {err}");
+        }
+        response["result"]
+            .as_str()
+            .context("invalid eth_subscribe response")?;
+
+        Ok(subscription)
+    }
+
+    /// Waits for the next new head.
+    async fn next(&mut self) -> Result<Header> {
+        loop {
+            let message = self.message().await?;
+            if message["method"] != "eth_subscription" {
+                continue;
+            }
+
+            let head = &message["params"]["result"];
+            return Ok(Header {
+                number: quantity(&head["number"])?,
+                hash: digest(&head["hash"])?,
+                parent_hash: digest(&head["parentHash"])?,
+                timestamp: quantity(&head["timestamp"])?,
+            });
+        }
+    }
+
+    /// Receives the next JSON message from the node.
+    async fn message(&mut self) -> Result<Json> {
+        loop {
+            let message = self.stream.next().await.context("subscription closed")??;
+            match message {
+                Message::Text(text) => return Ok(serde_json::from_str(&text)?),
+                Message::Binary(data) => return Ok(serde_json::from_slice(&data)?),
+                Message::Close(_) => bail!("subscription closed"),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn quantity(value: &Json) -> Result<U256> {
+    let value = value.as_str().context("expected quantity")?;
+    let value = value.strip_prefix("0x").context("expected hex quantity")?;
+    Ok(U256::from_str_radix(value, 16)?)
+}
+
+fn digest(value: &Json) -> Result<Digest> {
+    let value = value.as_str().context("expected hash")?;
+    let bytes = hex::decode(value)?;
+    Ok(Digest(
+        bytes
+            .try_into()
+            .map_err(|_| anyhow!("invalid hash length"))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::net::TcpListener};
+
+    fn header(number: u64, fork: u8) -> Header {
+        let hash = |number: u64| {
+            let mut hash = [fork; 32];
+            hash[..8].copy_from_slice(&number.to_be_bytes());
+            Digest(hash)
+        };
+        Header {
+            number: U256::from(number),
+            hash: hash(number),
+            parent_hash: hash(number - 1),
+            timestamp: U256::from(1_600_000_000 + number * 12),
+        }
+    }
+
+    /// Starts a WebSocket node stand-in that accepts a single `newHeads`
+    /// subscription, emits the scripted heads and then closes the connection.
+    async fn node(heads: Vec<Header>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            let request = stream.next().await.unwrap().unwrap();
+            let request = serde_json::from_str::<Json>(request.to_text().unwrap()).unwrap();
+            assert_eq!(request["method"], "eth_subscribe");
+            assert_eq!(request["params"], json!(["newHeads"]));
+            let response = json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": "0x1",
+            });
+            stream
+                .send(Message::Text(response.to_string()))
+                .await
+                .unwrap();
+
+            for head in heads {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "eth_subscription",
+                    "params": {
+                        "subscription": "0x1",
+                        "result": {
+                            "number": format!("{:#x}", head.number),
+                            "hash": hex::encode(&head.hash.0),
+                            "parentHash": hex::encode(&head.parent_hash.0),
+                            "timestamp": format!("{:#x}", head.timestamp),
+                        },
+                    },
+                });
+                stream
+                    .send(Message::Text(notification.to_string()))
+                    .await
+                    .unwrap();
+            }
+            stream.close(None).await.unwrap();
+        });
+
+        url.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn receives_new_heads() {
+        // Include a reorg of block 2 to a different fork.
+        let scripted = vec![header(1, 0), header(2, 0), header(2, 1), header(3, 1)];
+        let mut subscription = Subscription::connect(&node(scripted.clone()).await)
+            .await
+            .unwrap();
+
+        for expected in scripted {
+            assert_eq!(subscription.next().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_polling() {
+        let mut heads = Heads::new(node(vec![header(1, 0)]).await);
+        heads.wait(Duration::ZERO).await;
+        assert!(heads.subscription.is_some());
+
+        // The node closes the subscription after the scripted heads.
+        heads.wait(Duration::ZERO).await;
+        assert!(heads.subscription.is_none());
+
+        let start = Instant::now();
+        heads.wait(Duration::from_millis(10)).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
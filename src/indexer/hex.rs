@@ -0,0 +1,57 @@
+//! Hex encoding of bytes. Node requests and responses and configured values
+//! use `0x`-prefixed hex strings for byte data.
+
+use anyhow::{bail, ensure, Context, Result};
+
+/// Encodes bytes as a `0x`-prefixed lower case hex string.
+pub fn encode(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push(DIGITS[(byte >> 4) as usize] as char);
+        hex.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// Decodes a `0x`-prefixed hex string into bytes.
+pub fn decode(value: &str) -> Result<Vec<u8>> {
+    let digits = value.strip_prefix("0x").context("expected 0x prefix")?;
+    ensure!(digits.len() % 2 == 0, "odd number of hex digits");
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+fn digit(digit: u8) -> Result<u8> {
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => bail!("invalid hex digit"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        assert_eq!(encode(&[]), "0x");
+        assert_eq!(encode(&[0x01, 0xab, 0xff]), "0x01abff");
+        assert_eq!(decode("0x01abFF").unwrap(), [0x01, 0xab, 0xff]);
+        assert_eq!(decode("0x").unwrap(), []);
+    }
+
+    #[test]
+    fn rejects_invalid_hex() {
+        for value in ["01", "0x1", "0xgg", "0xéé", "0x+1"] {
+            assert!(decode(value).is_err(), "{value}");
+        }
+    }
+}
@@ -1,28 +1,78 @@
 //! Ethereum event indexer for a collection of events.
 
 mod adapter;
+mod call;
 mod chain;
+mod factory;
+mod failover;
+#[cfg(test)]
+mod fake;
+mod filter;
+mod heads;
+mod hex;
+mod node;
+mod retry;
+mod snapshot;
+mod trace;
 
+pub use self::{failover::Failover, node::Tracer, retry::Retry};
 use {
-    self::{adapter::Adapter, chain::Chain},
+    self::{
+        adapter::Adapter,
+        call::{Call, CallAdapter},
+        chain::Chain,
+        factory::Link,
+        heads::Heads,
+        node::{BlockId, Frame, Header, Node},
+        retry::Backoff,
+        snapshot::{Snapshot, SnapshotAdapter},
+    },
     crate::{
         config,
         database::{self, Database},
     },
-    anyhow::{Context, Result},
-    ethrpc::{
-        eth,
-        types::{Block, BlockTag, Hydrated, LogBlocks},
+    anyhow::{ensure, Context, Result},
+    ethrpc::types::{Digest, LogBlocks, LogFilter},
+    futures::{future, stream, StreamExt, TryStreamExt},
+    solabi::ethprim::{Address, U256},
+    std::{
+        cmp,
+        collections::{BTreeSet, HashMap, HashSet},
+        fmt::{self, Display, Formatter},
+        iter,
+        time::Duration,
     },
-    std::{cmp, time::Duration},
     tokio::time,
+    url::Url,
 };
 
+/// The maximum number of blocks to fetch headers, transactions or receipts for
+/// in a single batch request. Blocks with their transactions and receipts are
+/// large, so call and snapshot adapters fetch the blocks of a page in chunks.
+const BLOCK_BATCH_SIZE: usize = 50;
+
+/// The maximum number of blocks to trace in a single batch request. Traces of
+/// busy blocks are large and slow to compute, so they are requested in smaller
+/// batches, of which at most `concurrency` run at once.
+const TRACE_BATCH_SIZE: usize = 10;
+
+/// How far behind the latest block `sync` needs to be in order to catch up
+/// with a range of blocks at once instead of block by block.
+const CATCH_UP_DISTANCE: u64 = 8;
+
 /// An Ethereum event indexer.
-pub struct Indexer<D> {
-    eth: ethrpc::http::Client,
+pub struct Indexer<N, D> {
+    eth: N,
     database: D,
     adapters: Vec<Adapter>,
+    links: Vec<Link>,
+    calls: Vec<CallAdapter>,
+    snapshots: Vec<SnapshotAdapter>,
+    /// Whether each event, call and snapshot, in the order of
+    /// [`Self::init_blocks`], is being backfilled in the background instead
+    /// of being synced with new blocks.
+    backfilling: Vec<bool>,
+    heads: Option<Heads>,
 }
 
 /// The indexer run configuration.
@@ -30,60 +80,258 @@ pub struct Indexer<D> {
 pub struct Run {
     /// The block page size to use when fetching historic event data. Using
     /// larger values will speed up initialization, but may cause issues if too
-    /// many events are fetched per page.
+    /// many events are fetched per page. Pages that the node rejects are
+    /// split, so this is the maximum page size that will be used.
     pub page_size: u64,
-    /// The poll interval to use when checking for new blocks.
+    /// The number of pages to fetch concurrently when fetching historic event
+    /// data. Pages are still stored in order.
+    pub concurrency: usize,
+    /// The poll interval to use when checking for new blocks. When subscribed
+    /// to new heads, this is only used while the subscription is unavailable.
     pub poll_interval: Duration,
+    /// How to determine which blocks are final.
+    pub finality: Finality,
+    /// The policy for retrying transient node and database errors.
+    pub retry: Retry,
+    /// Whether to cross-check block hashes between node endpoints before
+    /// handling a reorg. This prevents a single lagging or forked endpoint
+    /// from causing spurious reorgs.
+    pub cross_check: bool,
+    /// Whether logs that fail to decode are a fatal error. Otherwise, they
+    /// are stored in the database as undecoded logs.
+    pub fatal_decode_errors: bool,
+    /// The node method used for tracing blocks, or `None` if the node does
+    /// not support tracing. Internal calls and value transfers are only
+    /// indexed from traces.
+    pub traces: Option<Tracer>,
+    /// Whether to stop once the initial indexing is done, instead of
+    /// continuing to sync new blocks. Initialization finishes when every
+    /// event is indexed until its end block or the finalized block.
+    pub once: bool,
+}
+
+/// How the indexer determines the finalized block. Blocks up until the
+/// finalized block are never expected to be reorged.
+#[derive(Clone, Copy, Debug)]
+pub enum Finality {
+    /// The block with the `finalized` tag.
+    Finalized,
+    /// The block with the `safe` tag.
+    Safe,
+    /// The latest block minus a number of confirmations, for nodes and chains
+    /// that don't support the other block tags.
+    Confirmations(u64),
+}
+
+impl Display for Finality {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Finalized => f.write_str("finalized"),
+            Self::Safe => f.write_str("safe"),
+            Self::Confirmations(confirmations) => write!(f, "{confirmations} confirmations"),
+        }
+    }
 }
 
-impl<D> Indexer<D>
+impl<N, D> Indexer<N, D>
 where
+    N: Node,
     D: Database,
 {
     /// Creates a new event indexer.
     pub fn create(
-        eth: ethrpc::http::Client,
+        eth: N,
         database: D,
         events: Vec<config::Event>,
+        calls: Vec<config::Call>,
+        transfers: Vec<config::Transfer>,
+        snapshots: Vec<config::Snapshot>,
     ) -> Result<Self> {
+        let adapters = events
+            .into_iter()
+            .map(Adapter::new)
+            .collect::<Result<Vec<_>>>()?;
+        let links = Link::resolve(&adapters)?;
+        let calls = calls
+            .into_iter()
+            .map(CallAdapter::new)
+            .chain(transfers.into_iter().map(CallAdapter::transfer))
+            .collect::<Result<Vec<_>>>()?;
+        let snapshots = snapshots
+            .into_iter()
+            .map(SnapshotAdapter::new)
+            .collect::<Result<Vec<_>>>()?;
+        let backfilling = vec![false; adapters.len() + calls.len() + snapshots.len()];
         Ok(Self {
             eth,
             database,
-            adapters: events
-                .into_iter()
-                .map(Adapter::new)
-                .collect::<Result<_>>()?,
+            adapters,
+            links,
+            calls,
+            snapshots,
+            backfilling,
+            heads: None,
         })
     }
 
+    /// Subscribes to new heads over a WebSocket connection to the node, so
+    /// that new blocks are synced as soon as they are announced instead of
+    /// polling for them.
+    pub fn subscribe(&mut self, url: Url) {
+        self.heads = Some(Heads::new(url));
+    }
+
     /// Runs the indexer, continuously fetching updates from the blockchain and
     /// storing them into the database.
+    ///
+    /// Transient errors are retried according to the configured retry policy.
+    /// Since all database updates are atomic, failed operations can always be
+    /// retried from the start.
     pub async fn run(mut self, config: Run) -> Result<()> {
-        let finalized = self.init(config).await?;
+        let mut backoff = Backoff::new(config.retry);
+        let finalized = loop {
+            match self.init(config).await {
+                Ok(finalized) => break finalized,
+                Err(err) => backoff.failed(err).await?,
+            }
+        };
+        if config.once {
+            for name in names(&self.adapters, &self.calls, &self.snapshots) {
+                let block = self.database.event_block(name).await?;
+                tracing::info!(event = %name, indexed = %block.indexed, "indexed event");
+            }
+            tracing::info!(finalized = %finalized.number, "finished indexing");
+            return Ok(());
+        }
+
         let mut chain = Chain::new(finalized.number, finalized.hash);
+        let mut page_size = config.page_size;
+        backoff.reset();
         loop {
-            if !self.sync(&mut chain).await? {
-                time::sleep(config.poll_interval).await;
+            match self.step(config, &mut chain, &mut page_size).await {
+                Ok(updated) => {
+                    backoff.reset();
+                    if !updated {
+                        self.wait(config).await;
+                    }
+                }
+                Err(err) => backoff.failed(err).await?,
+            }
+        }
+    }
+
+    /// Runs a single iteration of the indexing loop. Returns `true` if new
+    /// blockchain state was processed.
+    async fn step(&mut self, config: Run, chain: &mut Chain, page_size: &mut u64) -> Result<bool> {
+        // Sync on a copy of the local chain, so that it only gets updated if
+        // the new blockchain state was also stored in the database.
+        let mut next = chain.clone();
+        let updated = self.sync(config, &mut next).await?;
+        *chain = next;
+
+        // Events that are still being backfilled get a single page of
+        // historic blocks in between, so that syncing new blocks isn't
+        // held up by a long backfill.
+        if self.backfilling.contains(&true) {
+            let config = Run {
+                concurrency: 1,
+                ..config
             };
+            self.backfill(config, chain, page_size).await?;
+            return Ok(true);
+        }
+        Ok(updated)
+    }
+
+    /// Removes the indexed data of an event, call or snapshot starting at the
+    /// `from` block and rewinds its indexed block, in a single database
+    /// transaction. The removed blocks are indexed again the next time the
+    /// indexer runs.
+    ///
+    /// The configured filter is recorded for the event afterwards, so that a
+    /// changed filter applies from the `from` block on.
+    pub async fn reindex(&mut self, name: &str, from: u64) -> Result<()> {
+        ensure!(from > 0, "can't reindex the genesis block");
+        let signature = self
+            .adapters
+            .iter()
+            .map(|adapter| (adapter.name(), adapter.signature()))
+            .chain(
+                self.calls
+                    .iter()
+                    .map(|call| (call.name(), call.signature())),
+            )
+            .chain(
+                self.snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.name(), snapshot.signature())),
+            )
+            .find_map(|(event, signature)| (event == name).then_some(signature))
+            .with_context(|| format!("unknown event {name}"))?;
+        self.database.prepare_event(name, signature).await?;
+
+        let block = self.database.event_block(name).await?;
+        if block.indexed < from {
+            tracing::info!(event = %name, indexed = %block.indexed, "nothing to reindex");
+        } else {
+            self.database
+                .remove(&[database::Uncle {
+                    event: name,
+                    number: from,
+                }])
+                .await?;
+            tracing::info!(event = %name, %from, "removed indexed data for reindexing");
+        }
+
+        // Keep the recorded start block, so that a start block that moved
+        // earlier is still handled on startup.
+        let mut filter = names(&self.adapters, &self.calls, &self.snapshots)
+            .zip(self.filters())
+            .find_map(|(event, filter)| (event == name).then_some(filter))
+            .expect("missing filter");
+        if let Some(recorded) = self.database.event_filter(name).await? {
+            filter.start = recorded.start;
+        }
+        self.database.set_event_filter(name, &filter).await
+    }
+
+    /// Waits for a new block to become available.
+    async fn wait(&mut self, config: Run) {
+        match &mut self.heads {
+            Some(heads) => heads.wait(config.poll_interval).await,
+            None => time::sleep(config.poll_interval).await,
         }
     }
 
     /// Initializes an event indexer. This syncs historical event data and
-    /// ensures that all events are indexed up until the `finalized` block.
-    /// Returns the `finalized` block that it finished indexing until.
-    async fn init(&mut self, config: Run) -> Result<Block> {
+    /// ensures that all events are indexed up until the `finalized` block,
+    /// except for the ones that are far behind and get backfilled in the
+    /// background instead. Returns the `finalized` block that it finished
+    /// indexing until.
+    async fn init(&mut self, config: Run) -> Result<Header> {
         for adapter in &self.adapters {
             self.database
                 .prepare_event(adapter.name(), adapter.signature())
                 .await?;
         }
+        for call in &self.calls {
+            self.database
+                .prepare_event(call.name(), call.signature())
+                .await?;
+        }
+        for snapshot in &self.snapshots {
+            self.database
+                .prepare_event(snapshot.name(), snapshot.signature())
+                .await?;
+        }
+        self.check_filters(config).await?;
 
         let mut unfinalized = Vec::new();
-        for adapter in self.adapters.iter() {
-            let block = self.database.event_block(adapter.name()).await?;
+        for name in names(&self.adapters, &self.calls, &self.snapshots) {
+            let block = self.database.event_block(name).await?;
             if block.indexed > block.finalized {
                 unfinalized.push(database::Uncle {
-                    event: adapter.name(),
+                    event: name,
                     number: block.finalized + 1,
                 });
             }
@@ -98,210 +346,2541 @@ where
             self.database.remove(&unfinalized).await?;
         }
 
+        // Load the contracts discovered from factory events that were already
+        // indexed.
+        for link in &self.links {
+            let children = self
+                .database
+                .event_addresses(self.adapters[link.factory].name(), link.field)
+                .await?;
+            self.adapters[link.child].set_children(children);
+        }
+
+        // Events, calls and snapshots that are far behind the others, for
+        // example because they were just added with an early start block, are
+        // backfilled in the background while the others are synced. Contracts
+        // discovered by a factory are only known once their factory event is
+        // indexed, so these are backfilled along with their factory.
+        let blocks = self.init_blocks().await?;
+        let ends = self.end_blocks();
+        let active = |block: u64, end: Option<u64>| end.map_or(true, |end| block <= end);
+        let latest = iter::zip(&blocks, &ends)
+            .filter(|(block, end)| active(**block, **end))
+            .map(|(block, _)| *block)
+            .max()
+            .unwrap_or_default();
+        self.backfilling = iter::zip(&blocks, &ends)
+            .map(|(block, end)| {
+                !config.once && active(*block, *end) && block + config.page_size <= latest
+            })
+            .collect();
+        for link in &self.links {
+            self.backfilling[link.child] |= self.backfilling[link.factory];
+        }
+        for (name, block) in names(&self.adapters, &self.calls, &self.snapshots)
+            .zip(&blocks)
+            .zip(&self.backfilling)
+            .filter(|(_, backfill)| **backfill)
+            .map(|(name, _)| name)
+        {
+            tracing::info!(event = %name, from = %block, "backfilling in the background");
+        }
+
+        let include = self
+            .backfilling
+            .iter()
+            .map(|backfill| !backfill)
+            .collect::<Vec<_>>();
+        let mut page_size = config.page_size;
         loop {
-            let finalized = self
-                .eth
-                .execute(
-                    eth::GetBlockByNumber,
-                    (BlockTag::Finalized.into(), Hydrated::No),
-                )
+            let finalized = self.finalized(config.finality).await?;
+            if !self
+                .index_pages(config, finalized.number.as_u64(), &include, &mut page_size)
                 .await?
-                .context("missing finalized block")?;
-
-            // Compute the next block to initialize from per adapter and the
-            // earliest initialization block.
-            let init = self.init_blocks().await?;
-            let earliest = init
-                .iter()
-                .copied()
-                .min()
-                .unwrap_or(finalized.number.as_u64());
-            if finalized.number.as_u64() <= earliest {
+            {
                 return Ok(finalized);
             }
+        }
+    }
+
+    /// Indexes the next pages of historic blocks up until the `finalized`
+    /// block for the events, calls and snapshots selected by `include`, in
+    /// the order of [`Self::init_blocks`]. Returns `false` once they are all
+    /// indexed until the finalized block or their end block.
+    async fn index_pages(
+        &mut self,
+        config: Run,
+        finalized: u64,
+        include: &[bool],
+        page_size: &mut u64,
+    ) -> Result<bool> {
+        // Compute the next block to initialize from per adapter and the
+        // earliest initialization block. Adapters that were indexed past
+        // their end block are done.
+        let blocks = self.init_blocks().await?;
+        let ends = self.end_blocks();
+        let init = &blocks[..self.adapters.len()];
+        let active = iter::zip(&blocks, &ends)
+            .zip(include)
+            .filter(|(_, include)| **include)
+            .map(|(bounds, _)| bounds)
+            .filter(|(block, end)| end.map_or(true, |end| **block <= end))
+            .collect::<Vec<_>>();
+        let earliest = active
+            .iter()
+            .map(|(block, _)| **block)
+            .min()
+            .unwrap_or(finalized);
+        if finalized <= earliest {
+            return Ok(false);
+        }
+        let last = active
+            .iter()
+            .map(|(_, end)| end.unwrap_or(u64::MAX))
+            .max()
+            .unwrap_or(u64::MAX);
 
-            let to = cmp::min(finalized.number.as_u64(), earliest + config.page_size - 1);
-            tracing::debug!(from =% earliest, %to, "indexing blocks");
+        // Split the blocks to index into consecutive pages and compute
+        // the block ranges for the adapters that need them.
+        let mut pages = Vec::new();
+        let mut from = earliest;
+        while pages.len() < config.concurrency.max(1) && from < finalized && from <= last {
+            let to = cmp::min(finalized, from + *page_size - 1);
+            tracing::debug!(%from, %to, "indexing blocks");
 
-            // Prepare `eth_getLogs` queries, noting the indices of their
-            // corresponding adapters for decoding responses.
-            let (adapters, queries) = self
-                .adapters
-                .iter()
-                .zip(init.iter().copied())
-                .filter(|(_, from)| *from <= to)
-                .map(|(adapter, from)| {
-                    (
-                        adapter,
-                        (
-                            eth::GetLogs,
-                            (adapter.filter(LogBlocks::Range {
-                                from: from.into(),
-                                to: to.into(),
-                            }),),
-                        ),
-                    )
-                })
-                .unzip::<_, _, Vec<_>, Vec<_>>();
-            let results = self.eth.batch(queries).await?;
+            let [ranges, call_ranges, snapshot_ranges] =
+                self.page_ranges(&blocks, &ends, include, from, to);
+            pages.push((to, ranges, call_ranges, snapshot_ranges));
+            from = to + 1;
+        }
+
+        // Fetch the pages concurrently.
+        let results = future::try_join_all(pages.iter().map(
+            |(_, ranges, call_ranges, snapshot_ranges)| async move {
+                tokio::try_join!(
+                    self.fetch_adapter_logs(ranges),
+                    self.fetch_range_calls(config, call_ranges),
+                    self.fetch_range_snapshots(snapshot_ranges),
+                )
+            },
+        ))
+        .await?;
+
+        // Shrink the page size when the node rejected a page, and grow it
+        // back again while pages succeed.
+        let adjusted = match results.iter().filter_map(|((_, split), ..)| *split).min() {
+            Some(size) => size,
+            None => cmp::min(*page_size * 2, config.page_size),
+        };
+        if adjusted != *page_size {
+            tracing::debug!(%adjusted, "adjusted page size");
+            *page_size = adjusted;
+        }
+
+        // Commit the pages in order, so that the indexed block of each
+        // event only ever covers contiguous block ranges. For each page:
+        // - Discover contracts from factory events and fetch their logs
+        // - Update latest indexed blocks for the events that were queried
+        // - Add the logs to the DB.
+        //
+        // Pages were fetched before contracts discovered in earlier pages
+        // of this round were known, so we keep track of the block up until
+        // which their logs were fetched.
+        let mut fetched = HashMap::<(usize, Address), u64>::new();
+        for ((to, ranges, call_ranges, snapshot_ranges), ((mut logs, _), calls, snapshots)) in
+            pages.iter().zip(results)
+        {
+            let to = *to;
+            let mut queries = Vec::new();
+            for (&(adapter, address), block) in &mut fetched {
+                if init[adapter] <= to && *block < to {
+                    queries.push((adapter, address, *block + 1, to));
+                    *block = to;
+                }
+            }
+
+            // New contracts need their logs from their creation block up
+            // until the block their event is indexed to after this page.
+            let children =
+                factory::discover(&self.adapters, &self.links, &logs, |adapter, address| {
+                    adapter.child(address).is_some()
+                });
+            for child in &children {
+                let indexed = if init[child.adapter] <= to {
+                    to
+                } else {
+                    init[child.adapter] - 1
+                };
+                let from = cmp::max(child.block, self.adapters[child.adapter].start());
+                if from <= indexed {
+                    queries.push((child.adapter, child.address, from, indexed));
+                }
+                fetched.insert((child.adapter, child.address), indexed);
+            }
+            self.fetch_child_logs(&mut logs, &queries).await?;
 
-            // Compute the database updates required:
-            // - Update latest indexed blocks for the events that were queried
-            // - Add the logs to the DB.
-            let blocks = adapters
+            let timestamps = self.block_timestamps(&logs).await?;
+            // The ranges end at the end blocks of the adapters, if any.
+            let blocks = ranges
                 .iter()
-                .copied()
-                .map(|adapter| database::EventBlock {
-                    event: adapter.name(),
-                    block: database::Block {
-                        indexed: to,
-                        finalized: finalized.number.as_u64(),
-                    },
+                .map(|(adapter, _, to)| (self.adapters[*adapter].name(), *to))
+                .chain(
+                    call_ranges
+                        .iter()
+                        .map(|(call, _, to)| (self.calls[*call].name(), *to)),
+                )
+                .chain(
+                    snapshot_ranges
+                        .iter()
+                        .map(|(snapshot, _, to)| (self.snapshots[*snapshot].name(), *to)),
+                )
+                .map(|(event, indexed)| database::EventBlock {
+                    event,
+                    block: database::Block { indexed, finalized },
                 })
                 .collect::<Vec<_>>();
-            let logs = adapters
-                .into_iter()
-                .zip(results)
-                .flat_map(|(adapter, logs)| database_logs(adapter, logs))
-                .collect::<Vec<_>>();
+            let (mut logs, undecoded) = database_logs(
+                &self.adapters,
+                logs,
+                &timestamps,
+                config.fatal_decode_errors,
+            )?;
+            let receipts = self.fetch_receipts(&logs).await?;
+            logs.extend(call::database_logs(
+                &self.calls,
+                calls,
+                config.fatal_decode_errors,
+            )?);
+            logs.extend(snapshot::database_logs(
+                &self.snapshots,
+                snapshots,
+                config.fatal_decode_errors,
+            )?);
+
+            self.database
+                .update(&blocks, &logs, &undecoded, &receipts)
+                .await?;
+            for child in children {
+                self.adapters[child.adapter].add_child(child.address, child.block);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Backfills the next pages of historic blocks for the events, calls and
+    /// snapshots that are being backfilled. Once they are indexed until the
+    /// finalized block, they join the synced ones. Returns `true` if they
+    /// joined.
+    async fn backfill(&mut self, config: Run, chain: &Chain, page_size: &mut u64) -> Result<bool> {
+        let include = self.backfilling.clone();
+        if self
+            .index_pages(config, chain.finalized().as_u64(), &include, page_size)
+            .await?
+        {
+            return Ok(false);
+        }
+        self.join(config, chain).await
+    }
 
-            self.database.update(&blocks, &logs).await?;
+    /// Joins the backfilled events, calls and snapshots with the synced ones,
+    /// by indexing the unfinalized blocks of the local chain for them. Their
+    /// logs are fetched by block number, so they are checked against the
+    /// local chain. Returns `false` if the unfinalized blocks changed in the
+    /// meantime, in which case joining is retried after the next sync.
+    async fn join(&mut self, config: Run, chain: &Chain) -> Result<bool> {
+        let finalized = chain.finalized().as_u64();
+        let head = chain.next().as_u64() - 1;
+        let blocks = self.init_blocks().await?;
+        let ends = self.end_blocks();
+        let [ranges, call_ranges, snapshot_ranges] =
+            self.page_ranges(&blocks, &ends, &self.backfilling, finalized, head);
+
+        let from = ranges
+            .iter()
+            .chain(&call_ranges)
+            .chain(&snapshot_ranges)
+            .map(|(_, from, _)| *from)
+            .min()
+            .unwrap_or(head + 1);
+        let headers = self.headers(from..=head).await?;
+        if headers
+            .iter()
+            .any(|header| chain.hash(header.number) != Some(header.hash))
+        {
+            tracing::debug!("unfinalized blocks changed; retrying join");
+            return Ok(false);
+        }
+
+        let ((mut logs, _), calls, snapshots) = tokio::try_join!(
+            self.fetch_adapter_logs(&ranges),
+            self.fetch_calls(config, &headers, &call_ranges),
+            self.fetch_snapshots(&headers, &snapshot_ranges),
+        )?;
+
+        // Fetch the logs of contracts created in the unfinalized blocks.
+        let children = factory::discover(&self.adapters, &self.links, &logs, |adapter, address| {
+            adapter.child(address).is_some()
+        });
+        let queries = children
+            .iter()
+            .filter(|child| self.backfilling[child.adapter])
+            .map(|child| (child.adapter, child.address, child.block, head))
+            .collect::<Vec<_>>();
+        self.fetch_child_logs(&mut logs, &queries).await?;
+
+        let hashes = headers
+            .iter()
+            .map(|header| (header.number.as_u64(), header.hash))
+            .collect::<HashMap<_, _>>();
+        if logs
+            .iter()
+            .flatten()
+            .any(|log| hashes.get(&log.block_number.as_u64()) != Some(&log.block_hash))
+        {
+            tracing::debug!("logs do not match unfinalized blocks; retrying join");
+            return Ok(false);
+        }
+
+        let blocks = names(&self.adapters, &self.calls, &self.snapshots)
+            .zip(&ends)
+            .zip(&self.backfilling)
+            .filter(|(_, backfill)| **backfill)
+            .map(|((event, end), _)| database::EventBlock {
+                event,
+                block: database::Block {
+                    indexed: cmp::min(head, end.unwrap_or(u64::MAX)),
+                    finalized,
+                },
+            })
+            .collect::<Vec<_>>();
+        let timestamps = headers
+            .iter()
+            .map(|header| (header.number.as_u64(), header.timestamp.as_u64()))
+            .collect::<HashMap<_, _>>();
+        let (mut logs, undecoded) = database_logs(
+            &self.adapters,
+            logs,
+            &timestamps,
+            config.fatal_decode_errors,
+        )?;
+        let receipts = self.fetch_receipts(&logs).await?;
+        logs.extend(call::database_logs(
+            &self.calls,
+            calls,
+            config.fatal_decode_errors,
+        )?);
+        logs.extend(snapshot::database_logs(
+            &self.snapshots,
+            snapshots,
+            config.fatal_decode_errors,
+        )?);
+
+        self.database
+            .update(&blocks, &logs, &undecoded, &receipts)
+            .await?;
+        for block in &blocks {
+            tracing::info!(event = %block.event, %head, "finished backfilling");
+        }
+        for child in children {
+            self.adapters[child.adapter].add_child(child.address, child.block);
         }
+        self.backfilling.fill(false);
+        Ok(true)
     }
 
     /// Synchronises more events. Returns `true` if new blockchain state was
     /// processed.
-    async fn sync(&mut self, chain: &mut Chain) -> Result<bool> {
-        // TODO(nlordell): Remove reorged blocks and update with new data in a
-        // single database transaction.
-
-        let next = match self
+    async fn sync(&mut self, config: Run, chain: &mut Chain) -> Result<bool> {
+        // Fetch the latest block along with the next one, to detect when we
+        // are far behind and should catch up with a range of blocks at once.
+        let mut headers = self
             .eth
-            .execute(eth::GetBlockByNumber, (chain.next().into(), Hydrated::No))
+            .headers(&[BlockId::Number(chain.next()), BlockId::Latest])
             .await?
-        {
-            Some(value) => value,
-            None => return Ok(false),
-        };
+            .into_iter();
+        let (mut pending, latest) = (headers.next().flatten(), headers.next().flatten());
+        if let (Some(first), Some(latest)) = (pending, latest) {
+            if latest.number >= first.number + U256::from(CATCH_UP_DISTANCE)
+                && first.parent_hash == chain.head()
+                && self.catch_up(config, chain, first, latest.number).await?
+            {
+                return Ok(true);
+            }
+        }
 
-        match chain.append(next.hash, next.parent_hash)? {
-            chain::Append::Ok => {
-                tracing::debug!(
-                    block = %next.number, hash = %next.hash,
-                    "found new block"
-                );
+        // Walk back the local chain until the next block connects to it. The
+        // logs of all reorged blocks are removed in the same database
+        // transaction that stores the new block, so that readers never see
+        // the reorged blocks removed without their replacement.
+        let mut uncle = None;
+        let next = loop {
+            let next = match pending.take() {
+                Some(header) if !self.disputed(config, chain, &header).await? => header,
+                _ => break None,
+            };
+
+            match chain.append(next.hash, next.parent_hash)? {
+                chain::Append::Ok => {
+                    tracing::debug!(
+                        block = %next.number, hash = %next.hash,
+                        "found new block"
+                    );
+                    break Some(next);
+                }
+                chain::Append::Reorg => {
+                    let block = next.number - 1;
+                    tracing::debug!(%block, hash = %next.parent_hash, "reorg");
+                    uncle = Some(block.as_u64());
+                }
             }
-            chain::Append::Reorg => {
-                let block = next.number - 1;
-                tracing::debug!(%block, hash = %next.parent_hash, "reorg");
+            pending = self.header(BlockId::Number(chain.next())).await?;
+        };
 
-                let uncles = self
-                    .adapters
-                    .iter()
-                    .map(|adapter| database::Uncle {
-                        event: adapter.name(),
-                        number: block.as_u64(),
-                    })
-                    .collect::<Vec<_>>();
-                self.database.remove(&uncles).await?;
-                return Ok(true);
+        let uncles = match uncle {
+            Some(number) => synced(
+                &self.adapters,
+                &self.calls,
+                &self.snapshots,
+                &self.backfilling,
+                number,
+                number,
+            )
+            .map(|(event, _)| database::Uncle { event, number })
+            .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        let Some(next) = next else {
+            // The replacement for the reorged blocks is not available (yet),
+            // so just remove them.
+            let Some(uncle) = uncle else {
+                return Ok(false);
+            };
+            self.database.remove(&uncles).await?;
+            for adapter in &mut self.adapters {
+                adapter.remove_children(uncle);
             }
+            return Ok(true);
+        };
+
+        let number = next.number.as_u64();
+        let filters = self
+            .adapters
+            .iter()
+            .zip(&self.backfilling)
+            .map(|(adapter, backfill)| match adapter.end() {
+                Some(end) if number > end => Vec::new(),
+                _ if number < adapter.start() || *backfill => Vec::new(),
+                _ => adapter.filters(LogBlocks::Hash(next.hash)),
+            })
+            .collect::<Vec<_>>();
+        let (finalized, results, calls, snapshots) = tokio::try_join!(
+            self.finalized(config.finality),
+            self.eth.logs(filters.iter().flatten().cloned().collect()),
+            self.fetch_calls(config, &[next], &self.call_ranges(number, number)),
+            self.fetch_snapshots(&[next], &self.snapshot_ranges(number, number)),
+        )?;
+        let mut results = results.into_iter();
+        let mut logs = filters
+            .iter()
+            .map(|filters| results.by_ref().take(filters.len()).flatten().collect())
+            .collect::<Vec<_>>();
+
+        // Fetch the logs of contracts created in this block. Contracts that
+        // were created in reorged blocks are no longer known.
+        let children = factory::discover(&self.adapters, &self.links, &logs, |adapter, address| {
+            adapter
+                .child(address)
+                .is_some_and(|block| uncle.map_or(true, |uncle| block < uncle))
+        });
+        let fetched = children
+            .iter()
+            .filter(|child| {
+                let adapter = &self.adapters[child.adapter];
+                !self.backfilling[child.adapter]
+                    && number >= adapter.start()
+                    && adapter.end().map_or(true, |end| number <= end)
+            })
+            .collect::<Vec<_>>();
+        let queries = fetched
+            .iter()
+            .map(|child| {
+                let adapter = &self.adapters[child.adapter];
+                adapter.contracts_filter(LogBlocks::Hash(next.hash), vec![child.address])
+            })
+            .collect();
+        for (child, results) in fetched.into_iter().zip(self.eth.logs(queries).await?) {
+            logs[child.adapter].extend(results);
         }
+        sort_logs(&mut logs);
 
-        let (finalized, results) = tokio::try_join!(
-            async {
-                self.eth
-                    .execute(
-                        eth::GetBlockByNumber,
-                        (BlockTag::Finalized.into(), Hydrated::No),
-                    )
-                    .await?
-                    .context("missing finalized block")
-            },
-            async {
-                self.eth
-                    .batch(
-                        self.adapters
-                            .iter()
-                            .map(|adapter| {
-                                (eth::GetLogs, (adapter.filter(LogBlocks::Hash(next.hash)),))
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                    .await
-                    .map_err(anyhow::Error::from)
+        // The finalized block can be ahead of the block being synced, for
+        // example when catching up with only a few confirmations, so never
+        // finalize past it.
+        let finalized = cmp::min(finalized.number, next.number);
+        if chain.finalize(finalized)? != finalized {
+            tracing::debug!(block = %finalized, "updated finalized block");
+        }
+
+        let blocks = synced(
+            &self.adapters,
+            &self.calls,
+            &self.snapshots,
+            &self.backfilling,
+            number,
+            number,
+        )
+        .map(|(event, indexed)| database::EventBlock {
+            event,
+            block: database::Block {
+                indexed,
+                finalized: finalized.as_u64(),
             },
+        })
+        .collect::<Vec<_>>();
+        let timestamps = HashMap::from([(number, next.timestamp.as_u64())]);
+        let (mut logs, undecoded) = database_logs(
+            &self.adapters,
+            logs,
+            &timestamps,
+            config.fatal_decode_errors,
         )?;
+        let receipts = self.fetch_receipts(&logs).await?;
+        logs.extend(call::database_logs(
+            &self.calls,
+            calls,
+            config.fatal_decode_errors,
+        )?);
+        logs.extend(snapshot::database_logs(
+            &self.snapshots,
+            snapshots,
+            config.fatal_decode_errors,
+        )?);
 
-        if chain.finalize(finalized.number)? != finalized.number {
-            tracing::debug!(
-                block = %finalized.number,
-                "updated finalized block"
-            );
+        self.database
+            .replace(&uncles, &blocks, &logs, &undecoded, &receipts)
+            .await?;
+        if let Some(uncle) = uncle {
+            for adapter in &mut self.adapters {
+                adapter.remove_children(uncle);
+            }
+        }
+        for child in children {
+            self.adapters[child.adapter].add_child(child.address, child.block);
         }
+        Ok(true)
+    }
 
-        let blocks = self
-            .adapters
+    /// Catches up with the chain head by processing a range of blocks,
+    /// starting with `first`, at once. Headers and logs for the range are each
+    /// fetched in a single batch. Returns `false` if the fetched range turned
+    /// out to be inconsistent, for example because of a concurrent reorg, in
+    /// which case nothing was processed.
+    async fn catch_up(
+        &mut self,
+        config: Run,
+        chain: &mut Chain,
+        first: Header,
+        latest: U256,
+    ) -> Result<bool> {
+        let from = first.number.as_u64();
+        let to = cmp::min(latest.as_u64(), from + config.page_size - 1);
+        let blocks = (from + 1..=to)
+            .map(|number| BlockId::Number(number.into()))
+            .collect::<Vec<_>>();
+
+        // Only keep the headers that form a chain.
+        let mut headers = vec![first];
+        for header in self.eth.headers(&blocks).await? {
+            match header {
+                Some(header)
+                    if Some(&header.parent_hash) == headers.last().map(|last| &last.hash) =>
+                {
+                    headers.push(header)
+                }
+                _ => break,
+            }
+        }
+        let to = headers.last().expect("empty headers").number.as_u64();
+        tracing::debug!(%from, %to, "catching up");
+
+        let ranges = block_ranges(
+            self.adapters
+                .iter()
+                .map(|adapter| (adapter.start(), adapter.end())),
+            from,
+            to,
+        )
+        .into_iter()
+        .filter(|(adapter, ..)| !self.backfilling[*adapter])
+        .collect::<Vec<_>>();
+        let (finalized, (mut logs, _), calls, snapshots) = tokio::try_join!(
+            self.finalized(config.finality),
+            self.fetch_adapter_logs(&ranges),
+            self.fetch_calls(config, &headers, &self.call_ranges(from, to)),
+            self.fetch_snapshots(&headers, &self.snapshot_ranges(from, to)),
+        )?;
+
+        // Fetch the logs of contracts created in the range.
+        let children = factory::discover(&self.adapters, &self.links, &logs, |adapter, address| {
+            adapter.child(address).is_some()
+        });
+        let queries = children
             .iter()
-            .map(|adapter| database::EventBlock {
-                event: adapter.name(),
-                block: database::Block {
-                    indexed: next.number.as_u64(),
-                    finalized: finalized.number.as_u64(),
-                },
+            .filter(|child| !self.backfilling[child.adapter])
+            .filter_map(|child| {
+                let adapter = &self.adapters[child.adapter];
+                let from = cmp::max(child.block, adapter.start());
+                let to = cmp::min(to, adapter.end().unwrap_or(u64::MAX));
+                (from <= to).then_some((child.adapter, child.address, from, to))
             })
             .collect::<Vec<_>>();
-        let logs = self
-            .adapters
+        self.fetch_child_logs(&mut logs, &queries).await?;
+
+        // Logs are fetched by block number, so make sure that they are from
+        // the same blocks as the headers.
+        let hashes = headers
             .iter()
-            .zip(results)
-            .flat_map(|(adapter, logs)| database_logs(adapter, logs))
-            .collect::<Vec<_>>();
+            .map(|header| (header.number.as_u64(), header.hash))
+            .collect::<HashMap<_, _>>();
+        if logs
+            .iter()
+            .flatten()
+            .any(|log| hashes.get(&log.block_number.as_u64()) != Some(&log.block_hash))
+        {
+            tracing::debug!("logs do not match block headers; syncing block by block");
+            return Ok(false);
+        }
+
+        for header in &headers {
+            let append = chain.append(header.hash, header.parent_hash)?;
+            debug_assert_eq!(append, chain::Append::Ok);
+        }
+        let finalized = cmp::min(finalized.number, U256::from(to));
+        if chain.finalize(finalized)? != finalized {
+            tracing::debug!(block = %finalized, "updated finalized block");
+        }
+
+        let blocks = synced(
+            &self.adapters,
+            &self.calls,
+            &self.snapshots,
+            &self.backfilling,
+            from,
+            to,
+        )
+        .map(|(event, indexed)| database::EventBlock {
+            event,
+            block: database::Block {
+                indexed,
+                finalized: finalized.as_u64(),
+            },
+        })
+        .collect::<Vec<_>>();
+        let timestamps = headers
+            .iter()
+            .map(|header| (header.number.as_u64(), header.timestamp.as_u64()))
+            .collect::<HashMap<_, _>>();
+        let (mut logs, undecoded) = database_logs(
+            &self.adapters,
+            logs,
+            &timestamps,
+            config.fatal_decode_errors,
+        )?;
+        let receipts = self.fetch_receipts(&logs).await?;
+        logs.extend(call::database_logs(
+            &self.calls,
+            calls,
+            config.fatal_decode_errors,
+        )?);
+        logs.extend(snapshot::database_logs(
+            &self.snapshots,
+            snapshots,
+            config.fatal_decode_errors,
+        )?);
+
+        self.database
+            .update(&blocks, &logs, &undecoded, &receipts)
+            .await?;
+        for child in children {
+            self.adapters[child.adapter].add_child(child.address, child.block);
+        }
+        Ok(true)
+    }
+
+    /// Returns `true` if the next block does not connect to the local chain
+    /// but the node endpoints agree with the local chain, meaning that the
+    /// endpoint that returned the block is lagging or forked. Only checked
+    /// when cross-checking is enabled.
+    async fn disputed(&self, config: Run, chain: &Chain, next: &Header) -> Result<bool> {
+        if !config.cross_check || next.parent_hash == chain.head() {
+            return Ok(false);
+        }
 
-        self.database.update(&blocks, &logs).await?;
+        let parent = self.eth.consensus(next.number - 1).await?;
+        if parent.map(|parent| parent.hash) != Some(chain.head()) {
+            return Ok(false);
+        }
+
+        tracing::warn!(
+            block = %next.number, parent = %next.parent_hash,
+            "node endpoint disagrees with consensus; ignoring block"
+        );
         Ok(true)
     }
 
-    /// Computes the blocks to start initializing from for each adapter.
+    /// Computes the blocks to start initializing from for each adapter,
+    /// followed by each call adapter and each snapshot adapter.
     async fn init_blocks(&mut self) -> Result<Vec<u64>> {
+        let starts = self
+            .adapters
+            .iter()
+            .map(|adapter| (adapter.name(), adapter.start()))
+            .chain(self.calls.iter().map(|call| (call.name(), call.start())))
+            .chain(
+                self.snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.name(), snapshot.start())),
+            );
         let mut blocks = Vec::new();
-        for adapter in self.adapters.iter() {
+        for (name, start) in starts {
             blocks.push(cmp::max(
-                adapter.start(),
-                self.database.event_block(adapter.name()).await?.indexed + 1,
+                start,
+                self.database.event_block(name).await?.indexed + 1,
             ));
         }
         Ok(blocks)
     }
-}
 
-fn database_logs(
-    adapter: &Adapter,
-    logs: Vec<ethrpc::types::Log>,
-) -> impl Iterator<Item = database::Log> {
-    if !logs.is_empty() {
-        tracing::debug!(
-            event = %adapter.name(), logs = %logs.len(),
-            "fetched logs"
-        );
+    /// Returns the end blocks of each adapter, followed by each call adapter
+    /// and each snapshot adapter, in the same order as [`Self::init_blocks`].
+    fn end_blocks(&self) -> Vec<Option<u64>> {
+        self.adapters
+            .iter()
+            .map(Adapter::end)
+            .chain(self.calls.iter().map(CallAdapter::end))
+            .chain(self.snapshots.iter().map(SnapshotAdapter::end))
+            .collect()
+    }
+
+    /// Returns the filters of each adapter, followed by each call adapter and
+    /// each snapshot adapter, in the same order as [`Self::init_blocks`].
+    fn filters(&self) -> Vec<database::Filter> {
+        self.adapters
+            .iter()
+            .map(|adapter| (adapter.fingerprint(), adapter.start()))
+            .chain(
+                self.calls
+                    .iter()
+                    .map(|call| (call.fingerprint(), call.start())),
+            )
+            .chain(
+                self.snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.fingerprint(), snapshot.start())),
+            )
+            .map(|(fingerprint, start)| database::Filter { fingerprint, start })
+            .collect()
+    }
+
+    /// Compares the configured filters and start and end blocks with the ones
+    /// that the indexed data was stored with, and records the filters if they
+    /// changed. The recorded start block is the earliest block that data was
+    /// stored from.
+    ///
+    /// When the start block of an event moved earlier, the newly covered
+    /// blocks are indexed into its existing tables without rewinding the
+    /// blocks after them. Events linked by factories are rewound to the new
+    /// start block instead, as the contracts they discover depend on the
+    /// blocks in between. A start block that moved later keeps the data that
+    /// was already indexed. Changed filters and end blocks that moved before
+    /// the indexed block require an explicit reindex instead, as only the
+    /// user knows whether the indexed data should be dropped.
+    async fn check_filters(&mut self, config: Run) -> Result<()> {
+        let filters = self.filters();
+        let ends = self.end_blocks();
+        let names = names(&self.adapters, &self.calls, &self.snapshots)
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        for (index, (name, filter)) in iter::zip(&names, &filters).enumerate() {
+            let indexed = self.database.event_block(name).await?.indexed;
+            if let Some(end) = ends[index] {
+                ensure!(
+                    indexed <= end,
+                    "{name} is indexed until block {indexed} but ends at block {end}; run `arak \
+                     reindex --event {name} --from {}` to drop the blocks after it",
+                    end + 1
+                );
+            }
+
+            let Some(recorded) = self.database.event_filter(name).await? else {
+                self.database.set_event_filter(name, filter).await?;
+                continue;
+            };
+            ensure!(
+                recorded.fingerprint == filter.fingerprint,
+                "the filter of {name} changed from `{}` to `{}`; run `arak reindex --event \
+                 {name} --from <block>` with the first block that the new filter applies to",
+                recorded.fingerprint,
+                filter.fingerprint
+            );
+
+            // Block 0 is never indexed, see `init_blocks`.
+            let from = cmp::max(filter.start, 1);
+            let linked = self
+                .links
+                .iter()
+                .any(|link| link.factory == index || link.child == index);
+            if indexed < recorded.start || filter.start >= recorded.start {
+                // Nothing was stored from the recorded start block yet, so
+                // indexing continues from the configured one, or the data
+                // before the configured start block is kept.
+                if indexed >= from && filter.start < recorded.start {
+                    self.rewind(name, from).await?;
+                }
+                if indexed < recorded.start && &recorded != filter {
+                    self.database.set_event_filter(name, filter).await?;
+                }
+            } else if linked {
+                self.rewind(name, from).await?;
+                self.database.set_event_filter(name, filter).await?;
+            } else {
+                // The event is indexed until at least its recorded start
+                // block, so the newly covered blocks are before its end block.
+                tracing::info!(
+                    event = %name, start = %filter.start, previous = %recorded.start,
+                    "start block moved earlier, indexing the blocks before"
+                );
+                self.prepend(config, index, from, recorded.start).await?;
+            }
+        }
+        Ok(())
     }
 
-    logs.into_iter().filter_map(move |log| {
-        let fields = match adapter.decode(&log.topics, &log.data) {
-            Ok(fields) => fields,
-            Err(err) => {
-                tracing::warn!(?err, ?log, "failed to decode log");
-                return None;
+    /// Removes the indexed data of an event from the `from` block on, so that
+    /// it gets indexed again.
+    async fn rewind(&mut self, name: &str, from: u64) -> Result<()> {
+        tracing::info!(event = %name, %from, "start block moved earlier, reindexing");
+        self.database
+            .remove(&[database::Uncle {
+                event: name,
+                number: from,
+            }])
+            .await
+    }
+
+    /// Indexes the blocks `from..to` before the recorded start block of the
+    /// event, call or snapshot at `index` in the order of
+    /// [`Self::init_blocks`], into its existing tables. Pages are indexed from
+    /// the latest to the earliest, each recording the start block it was
+    /// indexed from in the same database transaction, so that an interrupted
+    /// run continues where it left off.
+    async fn prepend(&mut self, config: Run, index: usize, from: u64, to: u64) -> Result<()> {
+        let name = names(&self.adapters, &self.calls, &self.snapshots)
+            .nth(index)
+            .expect("missing event")
+            .to_owned();
+        let fingerprint = self.filters().swap_remove(index).fingerprint;
+        let (adapter_count, call_count) = (self.adapters.len(), self.calls.len());
+        let range = |offset: usize, len: usize, from: u64, to: u64| {
+            (offset..offset + len)
+                .contains(&index)
+                .then(|| (index - offset, from, to))
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        let mut page_size = config.page_size;
+        let mut next = to;
+        while next > from {
+            let start = cmp::max(from, next.saturating_sub(page_size));
+            let end = next - 1;
+            tracing::debug!(event = %name, from = %start, to = %end, "indexing blocks");
+            let ranges = range(0, adapter_count, start, end);
+            let call_ranges = range(adapter_count, call_count, start, end);
+            let snapshot_ranges =
+                range(adapter_count + call_count, self.snapshots.len(), start, end);
+            let ((logs, split), calls, snapshots) = tokio::try_join!(
+                self.fetch_adapter_logs(&ranges),
+                self.fetch_range_calls(config, &call_ranges),
+                self.fetch_range_snapshots(&snapshot_ranges),
+            )?;
+            if let Some(size) = split {
+                page_size = size;
             }
+
+            let timestamps = self.block_timestamps(&logs).await?;
+            let (mut logs, undecoded) = database_logs(
+                &self.adapters,
+                logs,
+                &timestamps,
+                config.fatal_decode_errors,
+            )?;
+            let receipts = self.fetch_receipts(&logs).await?;
+            logs.extend(call::database_logs(
+                &self.calls,
+                calls,
+                config.fatal_decode_errors,
+            )?);
+            logs.extend(snapshot::database_logs(
+                &self.snapshots,
+                snapshots,
+                config.fatal_decode_errors,
+            )?);
+
+            let filter = database::Filter {
+                fingerprint: fingerprint.clone(),
+                start,
+            };
+            self.database
+                .prepend(&name, &filter, &logs, &undecoded, &receipts)
+                .await?;
+            next = start;
+        }
+        Ok(())
+    }
+
+    /// Returns the inclusive block ranges to index in for each adapter, call
+    /// adapter and snapshot adapter selected by `include`. `blocks` and `ends`
+    /// are the blocks to start indexing from and the end blocks, in the order
+    /// of [`Self::init_blocks`].
+    fn page_ranges(
+        &self,
+        blocks: &[u64],
+        ends: &[Option<u64>],
+        include: &[bool],
+        from: u64,
+        to: u64,
+    ) -> [Vec<(usize, u64, u64)>; 3] {
+        let ranges = |offset: usize, len: usize| {
+            let bounds = iter::zip(&blocks[offset..][..len], &ends[offset..][..len])
+                .map(|(block, end)| (*block, *end));
+            block_ranges(bounds, from, to)
+                .into_iter()
+                .filter(|(index, ..)| include[offset + index])
+                .collect()
         };
+        let (adapters, calls) = (self.adapters.len(), self.calls.len());
+        [
+            ranges(0, adapters),
+            ranges(adapters, calls),
+            ranges(adapters + calls, self.snapshots.len()),
+        ]
+    }
 
-        Some(database::Log {
-            event: adapter.name(),
-            block_number: log.block_number.as_u64(),
-            log_index: log.log_index.as_u64(),
-            transaction_index: log.transaction_index.as_u64(),
-            address: log.address,
-            fields,
-        })
-    })
+    /// Returns the inclusive block ranges to index calls in for each synced
+    /// call adapter, taking their start and end blocks into account.
+    fn call_ranges(&self, from: u64, to: u64) -> Vec<(usize, u64, u64)> {
+        let backfill = &self.backfilling[self.adapters.len()..];
+        block_ranges(
+            self.calls.iter().map(|call| (call.start(), call.end())),
+            from,
+            to,
+        )
+        .into_iter()
+        .filter(|(call, ..)| !backfill[*call])
+        .collect()
+    }
+
+    /// Returns the inclusive block ranges to take snapshots in for each synced
+    /// snapshot adapter, taking their start and end blocks into account.
+    fn snapshot_ranges(&self, from: u64, to: u64) -> Vec<(usize, u64, u64)> {
+        let backfill = &self.backfilling[self.adapters.len() + self.calls.len()..];
+        block_ranges(
+            self.snapshots
+                .iter()
+                .map(|snapshot| (snapshot.start(), snapshot.end())),
+            from,
+            to,
+        )
+        .into_iter()
+        .filter(|(snapshot, ..)| !backfill[*snapshot])
+        .collect()
+    }
+
+    /// Fetches a single block header.
+    async fn header(&self, block: BlockId) -> Result<Option<Header>> {
+        Ok(self
+            .eth
+            .headers(&[block])
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+
+    /// Fetches the headers of the blocks with the specified numbers, in
+    /// batches of at most [`BLOCK_BATCH_SIZE`] blocks.
+    async fn headers(&self, numbers: impl IntoIterator<Item = u64>) -> Result<Vec<Header>> {
+        let numbers = numbers.into_iter().collect::<Vec<_>>();
+        let mut headers = Vec::with_capacity(numbers.len());
+        for numbers in numbers.chunks(BLOCK_BATCH_SIZE) {
+            let blocks = numbers
+                .iter()
+                .map(|number| BlockId::Number((*number).into()))
+                .collect::<Vec<_>>();
+            for (number, header) in numbers.iter().zip(self.eth.headers(&blocks).await?) {
+                headers.push(header.with_context(|| format!("missing block {number}"))?);
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Fetches the current finalized block header according to the finality
+    /// mode.
+    async fn finalized(&self, finality: Finality) -> Result<Header> {
+        let block = match finality {
+            Finality::Finalized => BlockId::Finalized,
+            Finality::Safe => BlockId::Safe,
+            Finality::Confirmations(confirmations) => {
+                let latest = self
+                    .header(BlockId::Latest)
+                    .await?
+                    .context("missing latest block")?;
+                BlockId::Number(latest.number.saturating_sub(confirmations.into()))
+            }
+        };
+        self.header(block)
+            .await?
+            .with_context(|| format!("missing {finality} block"))
+    }
+
+    /// Fetches logs for adapters over inclusive block ranges. The returned
+    /// logs are aligned with the adapters. Adapters without a block range, or
+    /// without any discovered contracts yet, have no logs. See
+    /// [`Self::fetch_logs`] for details on the returned split page size.
+    async fn fetch_adapter_logs(
+        &self,
+        ranges: &[(usize, u64, u64)],
+    ) -> Result<(Vec<Vec<ethrpc::types::Log>>, Option<u64>)> {
+        let (adapters, queries): (Vec<_>, Vec<_>) = ranges
+            .iter()
+            .flat_map(|&(adapter, from, to)| {
+                self.adapters[adapter]
+                    .filters(LogBlocks::default())
+                    .into_iter()
+                    .map(move |filter| (adapter, (filter, from, to)))
+            })
+            .unzip();
+        let (results, split) = self.fetch_logs(&queries).await?;
+
+        let mut logs = iter::repeat_with(Vec::new)
+            .take(self.adapters.len())
+            .collect::<Vec<_>>();
+        for (adapter, results) in adapters.into_iter().zip(results) {
+            logs[adapter].extend(results);
+        }
+        sort_logs(&mut logs);
+        Ok((logs, split))
+    }
+
+    /// Fetches the logs of newly discovered contracts over inclusive block
+    /// ranges and adds them to the logs of their adapters.
+    async fn fetch_child_logs(
+        &self,
+        logs: &mut [Vec<ethrpc::types::Log>],
+        children: &[(usize, Address, u64, u64)],
+    ) -> Result<()> {
+        // Contracts are not indexed past the end block of their adapter.
+        let (adapters, queries): (Vec<_>, Vec<_>) = children
+            .iter()
+            .filter_map(|&(adapter, address, from, to)| {
+                let to = cmp::min(to, self.adapters[adapter].end().unwrap_or(u64::MAX));
+                let filter =
+                    self.adapters[adapter].contracts_filter(LogBlocks::default(), vec![address]);
+                (from <= to).then_some((adapter, (filter, from, to)))
+            })
+            .unzip();
+        if queries.is_empty() {
+            return Ok(());
+        }
+
+        let (results, _) = self.fetch_logs(&queries).await?;
+        for (adapter, results) in adapters.into_iter().zip(results) {
+            logs[adapter].extend(results);
+        }
+        sort_logs(logs);
+        Ok(())
+    }
+
+    /// Fetches logs for filters over inclusive block ranges.
+    ///
+    /// Logs for all filters are fetched in a single batch. If the node
+    /// rejects it because of the size of the block ranges, each filter's
+    /// range is fetched individually and recursively split in half until the
+    /// node accepts it. In that case, the size of the smallest range that was
+    /// fetched is returned as well.
+    async fn fetch_logs(
+        &self,
+        queries: &[(LogFilter, u64, u64)],
+    ) -> Result<(Vec<Vec<ethrpc::types::Log>>, Option<u64>)> {
+        let filter = |filter: &LogFilter, from: u64, to: u64| LogFilter {
+            blocks: LogBlocks::Range {
+                from: from.into(),
+                to: to.into(),
+            },
+            ..filter.clone()
+        };
+
+        let err = match self
+            .eth
+            .logs(
+                queries
+                    .iter()
+                    .map(|(query, from, to)| filter(query, *from, *to))
+                    .collect(),
+            )
+            .await
+        {
+            Ok(results) => return Ok((results, None)),
+            Err(err) if node::is_log_range_error(&err) => err,
+            Err(err) => return Err(err),
+        };
+        tracing::debug!(?err, "node rejected log page; splitting");
+
+        let mut results = Vec::new();
+        let mut smallest = u64::MAX;
+        for (query, from, to) in queries {
+            let mut logs = Vec::new();
+            let mut ranges = vec![(*from, *to)];
+            while let Some((from, to)) = ranges.pop() {
+                match self.eth.logs(vec![filter(query, from, to)]).await {
+                    Ok(result) => {
+                        logs.extend(result.into_iter().flatten());
+                        smallest = cmp::min(smallest, to - from + 1);
+                    }
+                    Err(err) if from < to && node::is_log_range_error(&err) => {
+                        let mid = from + (to - from) / 2;
+                        tracing::debug!(%from, %to, "splitting log range");
+                        ranges.push((mid + 1, to));
+                        ranges.push((from, mid));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            results.push(logs);
+        }
+
+        Ok((results, Some(smallest)))
+    }
+
+    /// Fetches the calls for call adapters over inclusive block ranges of
+    /// finalized blocks. See [`Self::fetch_calls`] for details.
+    async fn fetch_range_calls(
+        &self,
+        config: Run,
+        ranges: &[(usize, u64, u64)],
+    ) -> Result<Vec<Call>> {
+        let numbers = ranges
+            .iter()
+            .flat_map(|&(_, from, to)| from..=to)
+            .collect::<BTreeSet<_>>();
+        let headers = self.headers(numbers).await?;
+        self.fetch_calls(config, &headers, ranges).await
+    }
+
+    /// Fetches the calls for call adapters in the specified blocks. `ranges`
+    /// contains the inclusive block ranges to index calls in for each call
+    /// adapter.
+    ///
+    /// Transactions and traces are fetched by block hash, so the calls are
+    /// guaranteed to be from the blocks with the specified headers. Adapters
+    /// that only index transactions don't need a tracing node.
+    async fn fetch_calls(
+        &self,
+        config: Run,
+        headers: &[Header],
+        ranges: &[(usize, u64, u64)],
+    ) -> Result<Vec<Call>> {
+        if ranges.is_empty() || headers.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (traced, untraced) = ranges
+            .iter()
+            .copied()
+            .partition::<Vec<_>, _>(|(adapter, ..)| self.calls[*adapter].traced());
+        let (mut calls, traced) = tokio::try_join!(
+            self.fetch_transaction_calls(headers, &untraced),
+            self.fetch_traced_calls(config, headers, &traced),
+        )?;
+
+        // Order the calls the same way as logs, by block and by their position
+        // within the block.
+        calls.extend(traced);
+        calls.sort_by_key(|call| (call.header.number.as_u64(), call.frame.position));
+        Ok(calls)
+    }
+
+    /// Fetches calls from the transactions of the specified blocks. The
+    /// success status of the matched transactions is taken from the receipts
+    /// of their blocks. Transactions and receipts are fetched in batches of
+    /// at most [`BLOCK_BATCH_SIZE`] blocks.
+    async fn fetch_transaction_calls(
+        &self,
+        headers: &[Header],
+        ranges: &[(usize, u64, u64)],
+    ) -> Result<Vec<Call>> {
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut calls = Vec::new();
+        for headers in headers.chunks(BLOCK_BATCH_SIZE) {
+            let hashes = headers.iter().map(|header| header.hash).collect::<Vec<_>>();
+            for (header, transactions) in headers.iter().zip(self.eth.transactions(&hashes).await?)
+            {
+                let frames = transactions
+                    .with_context(|| format!("missing block {}", header.hash))?
+                    .into_iter()
+                    .map(|transaction| Frame {
+                        transaction_hash: transaction.hash,
+                        transaction_index: transaction.index,
+                        position: transaction.index,
+                        depth: 0,
+                        from: transaction.from,
+                        to: transaction.to,
+                        value: transaction.value,
+                        input: transaction.input,
+                        success: false,
+                    })
+                    .collect::<Vec<_>>();
+                calls.extend(call::find(&self.calls, ranges, header, &frames));
+            }
+        }
+
+        let mut blocks = calls
+            .iter()
+            .map(|call| call.header.hash)
+            .collect::<Vec<_>>();
+        blocks.dedup();
+        let mut statuses = HashMap::new();
+        for blocks in blocks.chunks(BLOCK_BATCH_SIZE) {
+            for (hash, receipts) in blocks.iter().zip(self.eth.receipts(blocks).await?) {
+                let receipts =
+                    receipts.with_context(|| format!("missing receipts for block {hash}"))?;
+                statuses.extend(
+                    receipts
+                        .into_iter()
+                        .map(|receipt| (receipt.transaction_hash, receipt.success)),
+                );
+            }
+        }
+
+        // Transactions from before the Byzantium fork have no status, and
+        // are assumed to have succeeded.
+        for call in &mut calls {
+            let hash = call.frame.transaction_hash;
+            call.frame.success = statuses
+                .get(&hash)
+                .with_context(|| format!("missing receipt for transaction {hash}"))?
+                .unwrap_or(true);
+        }
+        Ok(calls)
+    }
+
+    /// Fetches calls, including internal ones, from the traces of the
+    /// specified blocks. Blocks are traced in batches of at most
+    /// [`TRACE_BATCH_SIZE`] blocks.
+    async fn fetch_traced_calls(
+        &self,
+        config: Run,
+        headers: &[Header],
+        ranges: &[(usize, u64, u64)],
+    ) -> Result<Vec<Call>> {
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let tracer = config
+            .traces
+            .context("internal calls and transfers require a trace source")?;
+        let traces = stream::iter(headers.chunks(TRACE_BATCH_SIZE))
+            .map(|headers| self.eth.traces(tracer, headers))
+            .buffered(config.concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut calls = Vec::new();
+        for (header, frames) in headers.iter().zip(traces.into_iter().flatten()) {
+            calls.extend(call::find(&self.calls, ranges, header, &frames));
+        }
+        Ok(calls)
+    }
+
+    /// Takes the snapshots for snapshot adapters over inclusive block ranges of
+    /// finalized blocks. See [`Self::fetch_snapshots`] for details.
+    async fn fetch_range_snapshots(&self, ranges: &[(usize, u64, u64)]) -> Result<Vec<Snapshot>> {
+        let numbers = ranges
+            .iter()
+            .flat_map(|&(snapshot, from, to)| self.snapshots[snapshot].blocks(from, to))
+            .collect::<BTreeSet<_>>();
+        let headers = self.headers(numbers).await?;
+        self.fetch_snapshots(&headers, ranges).await
+    }
+
+    /// Takes the snapshots for snapshot adapters at the specified blocks.
+    /// `ranges` contains the inclusive block ranges to take snapshots in for
+    /// each snapshot adapter.
+    ///
+    /// Calls are executed at the block hash, so the snapshots are guaranteed
+    /// to be of the state of the blocks with the specified headers.
+    async fn fetch_snapshots(
+        &self,
+        headers: &[Header],
+        ranges: &[(usize, u64, u64)],
+    ) -> Result<Vec<Snapshot>> {
+        let mut snapshots = headers
+            .iter()
+            .flat_map(|header| snapshot::schedule(&self.snapshots, ranges, header))
+            .collect::<Vec<_>>();
+        let calls = snapshots
+            .iter()
+            .map(|snapshot| self.snapshots[snapshot.adapter].call(snapshot))
+            .collect::<Vec<_>>();
+        for (snapshot, data) in snapshots.iter_mut().zip(self.eth.view_calls(&calls).await?) {
+            snapshot.data = data;
+        }
+        Ok(snapshots)
+    }
+
+    /// Fetches the receipts of the transactions that emitted logs of events
+    /// with receipts enabled. Receipts are fetched by block hash, so they are
+    /// guaranteed to be from the same blocks as the logs, in batches of at
+    /// most [`BLOCK_BATCH_SIZE`] blocks.
+    async fn fetch_receipts(&self, logs: &[database::Log<'_>]) -> Result<Vec<database::Receipt>> {
+        let events = self
+            .adapters
+            .iter()
+            .filter(|adapter| adapter.receipt())
+            .map(Adapter::name)
+            .collect::<HashSet<_>>();
+        let mut transactions = logs
+            .iter()
+            .filter(|log| events.contains(log.event))
+            .map(|log| (log.block_number, log.transaction_index, log.block_hash))
+            .collect::<Vec<_>>();
+        if transactions.is_empty() {
+            return Ok(Vec::new());
+        }
+        transactions.sort_by_key(|(number, index, _)| (*number, *index));
+        transactions.dedup_by_key(|(number, index, _)| (*number, *index));
+
+        let mut blocks = transactions
+            .iter()
+            .map(|(.., hash)| *hash)
+            .collect::<Vec<_>>();
+        blocks.dedup();
+        let mut receipts = HashMap::new();
+        for blocks in blocks.chunks(BLOCK_BATCH_SIZE) {
+            for (hash, block) in blocks.iter().zip(self.eth.receipts(blocks).await?) {
+                let block = block.with_context(|| format!("missing receipts for block {hash}"))?;
+                receipts.extend(
+                    block
+                        .into_iter()
+                        .map(|receipt| ((*hash, receipt.transaction_index), receipt)),
+                );
+            }
+        }
+
+        transactions
+            .into_iter()
+            .map(|(block_number, transaction_index, hash)| {
+                let receipt = receipts
+                    .remove(&(hash, transaction_index))
+                    .with_context(|| {
+                        format!(
+                            "missing receipt for transaction {transaction_index} in block {hash}"
+                        )
+                    })?;
+                Ok(database::Receipt {
+                    block_number,
+                    transaction_index,
+                    transaction_hash: receipt.transaction_hash,
+                    from: receipt.from,
+                    to: receipt.to,
+                    gas_used: receipt.gas_used,
+                    effective_gas_price: receipt.effective_gas_price,
+                    success: receipt.success,
+                })
+            })
+            .collect()
+    }
+
+    /// Fetches the timestamps of all blocks that the specified logs were
+    /// emitted in.
+    async fn block_timestamps(
+        &self,
+        logs: &[Vec<ethrpc::types::Log>],
+    ) -> Result<HashMap<u64, u64>> {
+        let numbers = logs
+            .iter()
+            .flatten()
+            .map(|log| log.block_number.as_u64())
+            .collect::<BTreeSet<_>>();
+        let headers = self.headers(numbers).await?;
+        Ok(headers
+            .into_iter()
+            .map(|header| (header.number.as_u64(), header.timestamp.as_u64()))
+            .collect())
+    }
+}
+
+/// Returns the names of all indexed events, calls and snapshots. These
+/// identify them in the database.
+fn names<'a>(
+    adapters: &'a [Adapter],
+    calls: &'a [CallAdapter],
+    snapshots: &'a [SnapshotAdapter],
+) -> impl Iterator<Item = &'a str> {
+    adapters
+        .iter()
+        .map(Adapter::name)
+        .chain(calls.iter().map(CallAdapter::name))
+        .chain(snapshots.iter().map(SnapshotAdapter::name))
+}
+
+/// Returns the names of the events, calls and snapshots that are synced with
+/// the new blocks `from..=to`, as opposed to being backfilled or having ended
+/// before them, along with the block they are indexed until after syncing.
+/// This is `to`, capped at their end block.
+fn synced<'a>(
+    adapters: &'a [Adapter],
+    calls: &'a [CallAdapter],
+    snapshots: &'a [SnapshotAdapter],
+    backfill: &'a [bool],
+    from: u64,
+    to: u64,
+) -> impl Iterator<Item = (&'a str, u64)> {
+    let ends = adapters
+        .iter()
+        .map(Adapter::end)
+        .chain(calls.iter().map(CallAdapter::end))
+        .chain(snapshots.iter().map(SnapshotAdapter::end));
+    names(adapters, calls, snapshots)
+        .zip(ends)
+        .zip(backfill)
+        .filter(move |((_, end), backfill)| !**backfill && end.map_or(true, |end| end >= from))
+        .map(move |((name, end), _)| (name, cmp::min(to, end.unwrap_or(u64::MAX))))
+}
+
+/// Returns the inclusive block ranges for adapters with the specified start
+/// and optional end blocks. Adapters that start after the range or end before
+/// it are skipped.
+fn block_ranges(
+    bounds: impl Iterator<Item = (u64, Option<u64>)>,
+    from: u64,
+    to: u64,
+) -> Vec<(usize, u64, u64)> {
+    bounds
+        .enumerate()
+        .map(|(index, (start, end))| {
+            let to = cmp::min(to, end.unwrap_or(u64::MAX));
+            (index, cmp::max(start, from), to)
+        })
+        .filter(|(_, from, to)| from <= to)
+        .collect()
+}
+
+/// Sorts the logs of each adapter in the order they were emitted in. This is
+/// needed after combining the logs of multiple filters of an adapter, or
+/// adding the logs of newly discovered contracts.
+fn sort_logs(logs: &mut [Vec<ethrpc::types::Log>]) {
+    for logs in logs {
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+    }
+}
+
+/// Converts logs fetched from the node for each adapter to database logs.
+/// `timestamps` must contain the timestamp of every block that the logs were
+/// emitted in. Logs that fail to decode are returned separately so that they
+/// can be kept for inspection, unless decoding errors are fatal.
+fn database_logs<'a>(
+    adapters: &'a [Adapter],
+    logs: Vec<Vec<ethrpc::types::Log>>,
+    timestamps: &HashMap<u64, u64>,
+    fatal_decode_errors: bool,
+) -> Result<(Vec<database::Log<'a>>, Vec<database::UndecodedLog<'a>>)> {
+    let mut decoded = Vec::new();
+    let mut undecoded = Vec::new();
+    for (adapter, logs) in adapters.iter().zip(logs) {
+        if !logs.is_empty() {
+            tracing::debug!(
+                event = %adapter.name(), logs = %logs.len(),
+                "fetched logs"
+            );
+        }
+
+        // Logs are returned in order, so we can count the event's logs per
+        // transaction as we go.
+        let mut transaction_logs = HashMap::<Digest, u64>::new();
+        for log in logs {
+            let ordinal = transaction_logs.entry(log.transaction_hash).or_default();
+            let index = *ordinal;
+            *ordinal += 1;
+
+            let fields = match adapter.decode(&log.topics, &log.data) {
+                Ok(fields) => fields,
+                Err(err) if fatal_decode_errors => {
+                    return Err(err.context(format!(
+                        "failed to decode log {} in block {} for event {}",
+                        log.log_index,
+                        log.block_number,
+                        adapter.name()
+                    )));
+                }
+                Err(err) => {
+                    tracing::warn!(?err, ?log, "failed to decode log");
+                    undecoded.push(database::UndecodedLog {
+                        event: adapter.name(),
+                        block_number: log.block_number.as_u64(),
+                        block_hash: log.block_hash,
+                        log_index: log.log_index.as_u64(),
+                        transaction_index: log.transaction_index.as_u64(),
+                        transaction_hash: log.transaction_hash,
+                        address: log.address,
+                        topics: log.topics.to_vec(),
+                        data: log.data.to_vec(),
+                        error: format!("{err:#}"),
+                    });
+                    continue;
+                }
+            };
+
+            let block_number = log.block_number.as_u64();
+            decoded.push(database::Log {
+                event: adapter.name(),
+                block_number,
+                block_timestamp: timestamps[&block_number],
+                block_hash: log.block_hash,
+                log_index: log.log_index.as_u64(),
+                transaction_index: log.transaction_index.as_u64(),
+                transaction_hash: log.transaction_hash,
+                event_ordinal: index,
+                address: log.address,
+                contract_label: adapter.contract_label(&log.address),
+                fields,
+            });
+        }
+    }
+    Ok((decoded, undecoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::database::Sqlite,
+        fake::{FakeLog, FakeTransaction},
+        solabi::ethprim::{keccak, Address},
+    };
+
+    fn run() -> Run {
+        Run {
+            page_size: 8,
+            concurrency: 1,
+            poll_interval: Duration::ZERO,
+            finality: Finality::Finalized,
+            retry: Retry {
+                initial_backoff: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+                max_retries: Some(0),
+            },
+            cross_check: false,
+            fatal_decode_errors: false,
+            traces: None,
+            once: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn splits_rejected_log_pages() {
+        let node = fake::Node::new();
+        for _ in 0..20 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        node.finalize(20);
+        node.limit_logs(3);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+
+        assert_eq!(finalized.number, 20);
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            20
+        );
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=20).collect::<Vec<_>>()
+        );
+
+        let queries = indexer.eth.log_queries();
+        assert_eq!(queries[..4], [(1, 8), (1, 8), (1, 4), (1, 2)]);
+        assert!(queries
+            .iter()
+            .any(|(from, to)| to - from + 1 == 4 && *from > 8));
+    }
+
+    #[tokio::test]
+    async fn fails_when_single_block_is_rejected() {
+        let node = fake::Node::new();
+        node.mine(vec![
+            FakeLog::new(Address::default(), &[keccak!("Foo()")]);
+            4
+        ]);
+        node.finalize(1);
+        node.limit_logs(3);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        assert!(indexer.init(run()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cross_checks_reorgs() {
+        let nodes = [fake::Node::new(), fake::Node::new(), fake::Node::new()];
+        let block = nodes.iter().map(|node| node.mine(vec![])).last().unwrap();
+        for node in &nodes[1..] {
+            node.mine(vec![]);
+        }
+
+        // The first endpoint is on a fork that the other endpoints disagree
+        // with.
+        nodes[0].reorg(1);
+        nodes[0].mine(vec![]);
+        nodes[0].mine(vec![]);
+
+        let mut indexer = Indexer::create(
+            Failover::new(nodes.into()),
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let config = Run {
+            cross_check: true,
+            ..run()
+        };
+        let finalized = indexer.init(config).await.unwrap();
+
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        chain.append(block.hash, block.parent_hash).unwrap();
+
+        assert!(!indexer.sync(config, &mut chain).await.unwrap());
+        assert_eq!(indexer.eth.current(), 1);
+        assert!(indexer.sync(config, &mut chain).await.unwrap());
+        assert_eq!(chain.next(), 3);
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_pages_concurrently() {
+        let node = fake::Node::new();
+        for _ in 0..20 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        node.finalize(20);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let config = Run {
+            page_size: 4,
+            concurrency: 3,
+            ..run()
+        };
+        indexer.init(config).await.unwrap();
+
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            20
+        );
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=20).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            indexer.eth.log_queries(),
+            [(1, 4), (5, 8), (9, 12), (13, 16), (17, 20)]
+        );
+    }
+
+    #[tokio::test]
+    async fn finalizes_with_confirmations() {
+        let node = fake::Node::new();
+        for _ in 0..10 {
+            node.mine(vec![]);
+        }
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let config = Run {
+            finality: Finality::Confirmations(3),
+            ..run()
+        };
+        let finalized = indexer.init(config).await.unwrap();
+        assert_eq!(finalized.number, 7);
+
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        for _ in 0..3 {
+            assert!(indexer.sync(config, &mut chain).await.unwrap());
+        }
+        assert!(!indexer.sync(config, &mut chain).await.unwrap());
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap(),
+            database::Block {
+                indexed: 10,
+                finalized: 7,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn replaces_reorged_blocks_atomically() {
+        let node = fake::Node::new();
+        node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(indexer.database.block_numbers("Foo"), [1, 2]);
+
+        indexer.eth.reorg(1);
+        indexer.eth.mine(vec![]);
+        indexer.eth.mine(vec![]);
+        indexer.eth.mine(vec![]);
+
+        // A single sync walks back to the common ancestor and replaces both
+        // reorged blocks with the new block 1.
+        assert!(indexer.sync(run(), &mut chain).await.unwrap());
+        assert_eq!(chain.next(), 2);
+        assert_eq!(indexer.database.block_numbers("Foo"), [] as [u64; 0]);
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn catches_up_with_block_ranges() {
+        let node = fake::Node::new();
+        for _ in 0..30 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+
+        assert_eq!(chain.next(), 31);
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=30).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            indexer.eth.log_queries(),
+            [
+                (1, 8),
+                (9, 16),
+                (17, 24),
+                (25, 25),
+                (26, 26),
+                (27, 27),
+                (28, 28),
+                (29, 29),
+                (30, 30),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn syncs_from_future_start_blocks() {
+        let node = fake::Node::new();
+        for _ in 0..30 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        node.finalize(20);
+
+        let mut event = config::Event::for_signature("event Foo()");
+        event.start = 25;
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![event],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        assert!(indexer.database.block_numbers("Foo").is_empty());
+
+        // Both catching up and syncing single blocks skip the blocks before
+        // the start block.
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(chain.next(), 31);
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (25..=30).collect::<Vec<_>>()
+        );
+        assert_eq!(indexer.eth.log_queries(), [(25, 28), (29, 29), (30, 30)]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_end_blocks() {
+        let node = fake::Node::new();
+        for _ in 0..30 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        node.finalize(20);
+
+        let mut event = config::Event::for_signature("event Foo()");
+        event.end = Some(12);
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![event],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        assert_eq!(finalized.number, 20);
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=12).collect::<Vec<_>>()
+        );
+
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(chain.next(), 31);
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=12).collect::<Vec<_>>()
+        );
+        assert_eq!(indexer.eth.log_queries(), [(1, 8), (9, 12)]);
+    }
+
+    #[tokio::test]
+    async fn caps_indexed_blocks_at_end_blocks() {
+        let node = fake::Node::new();
+        for _ in 0..30 {
+            node.mine(vec![
+                FakeLog::new(Address::default(), &[keccak!("Foo()")]),
+                FakeLog::new(Address::default(), &[keccak!("Bar()")]),
+            ]);
+        }
+        node.finalize(20);
+
+        let mut foo = config::Event::for_signature("event Foo()");
+        foo.end = Some(14);
+        let mut bar = config::Event::for_signature("event Bar()");
+        bar.end = Some(25);
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![foo, bar],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            14
+        );
+        assert_eq!(
+            indexer.database.event_block("Bar").await.unwrap().indexed,
+            20
+        );
+
+        // Syncing past the end blocks keeps the events indexed until them.
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(chain.next(), 31);
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            14
+        );
+        assert_eq!(
+            indexer.database.event_block("Bar").await.unwrap().indexed,
+            25
+        );
+        assert_eq!(
+            indexer.database.block_numbers("Bar"),
+            (1..=25).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn backfills_lagging_events_while_syncing() {
+        let node = fake::Node::new();
+        for _ in 0..30 {
+            node.mine(vec![
+                FakeLog::new(Address::default(), &[keccak!("Foo()")]),
+                FakeLog::new(Address::default(), &[keccak!("Bar()")]),
+            ]);
+        }
+        node.finalize(20);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+
+        // Add a new event, which is far behind the existing one.
+        let mut indexer = Indexer::create(
+            indexer.eth,
+            indexer.database,
+            vec![
+                config::Event::for_signature("event Foo()"),
+                config::Event::for_signature("event Bar()"),
+            ],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        assert_eq!(indexer.backfilling, [false, true]);
+        assert!(indexer.database.block_numbers("Bar").is_empty());
+
+        // Syncing new blocks continues while the new event is backfilled.
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        let mut page_size = run().page_size;
+        assert!(indexer.sync(run(), &mut chain).await.unwrap());
+        assert!(!indexer
+            .backfill(run(), &chain, &mut page_size)
+            .await
+            .unwrap());
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            28
+        );
+        assert_eq!(
+            indexer.database.event_block("Bar").await.unwrap().indexed,
+            8
+        );
+
+        while indexer.backfilling.contains(&true) {
+            indexer.sync(run(), &mut chain).await.unwrap();
+            indexer
+                .backfill(run(), &chain, &mut page_size)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            indexer.database.event_block("Bar").await.unwrap().indexed,
+            30
+        );
+
+        // Once joined, the new event is synced along with the existing one.
+        for _ in 0..2 {
+            indexer.eth.mine(vec![
+                FakeLog::new(Address::default(), &[keccak!("Foo()")]),
+                FakeLog::new(Address::default(), &[keccak!("Bar()")]),
+            ]);
+        }
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        for event in ["Foo", "Bar"] {
+            assert_eq!(
+                indexer.database.block_numbers(event),
+                (1..=32).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn interleaves_single_backfill_pages_with_syncing() {
+        let node = fake::Node::new();
+        for _ in 0..30 {
+            node.mine(vec![
+                FakeLog::new(Address::default(), &[keccak!("Foo()")]),
+                FakeLog::new(Address::default(), &[keccak!("Bar()")]),
+            ]);
+        }
+        node.finalize(20);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+
+        let mut indexer = Indexer::create(
+            indexer.eth,
+            indexer.database,
+            vec![
+                config::Event::for_signature("event Foo()"),
+                config::Event::for_signature("event Bar()"),
+            ],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let config = Run {
+            concurrency: 4,
+            ..run()
+        };
+        let finalized = indexer.init(config).await.unwrap();
+        assert_eq!(indexer.backfilling, [false, true]);
+
+        // Each iteration syncs new blocks and backfills a single page, even
+        // though more pages could be fetched concurrently.
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        let mut page_size = config.page_size;
+        for backfilled in [8, 16] {
+            assert!(indexer
+                .step(config, &mut chain, &mut page_size)
+                .await
+                .unwrap());
+            assert_eq!(
+                indexer.database.event_block("Bar").await.unwrap().indexed,
+                backfilled
+            );
+        }
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            29
+        );
+
+        while indexer.backfilling.contains(&true) {
+            indexer
+                .step(config, &mut chain, &mut page_size)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            indexer.database.event_block("Bar").await.unwrap().indexed,
+            30
+        );
+        assert!(!indexer
+            .step(config, &mut chain, &mut page_size)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn reindexes_events() {
+        let node = fake::Node::new();
+        for _ in 0..10 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        node.finalize(10);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature("event Foo()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        indexer.init(run()).await.unwrap();
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=10).collect::<Vec<_>>()
+        );
+
+        indexer.reindex("Foo", 5).await.unwrap();
+        assert_eq!(indexer.database.block_numbers("Foo"), [1, 2, 3, 4]);
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            4
+        );
+
+        // Blocks that are not indexed yet are left alone.
+        indexer.reindex("Foo", 8).await.unwrap();
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            4
+        );
+        assert!(indexer.reindex("Bar", 5).await.is_err());
+        assert!(indexer.reindex("Foo", 0).await.is_err());
+
+        indexer.init(run()).await.unwrap();
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (1..=10).collect::<Vec<_>>()
+        );
+        assert_eq!(indexer.eth.log_queries()[2..], [(5, 10)]);
+    }
+
+    #[tokio::test]
+    async fn detects_changed_filters_and_start_blocks() {
+        let node = fake::Node::new();
+        for _ in 0..10 {
+            node.mine(vec![FakeLog::new(Address::default(), &[keccak!("Foo()")])]);
+        }
+        node.finalize(10);
+
+        let event = |start: u64, contract: config::Contract| config::Event {
+            start,
+            contract,
+            ..config::Event::for_signature("event Foo()")
+        };
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![event(5, config::Contract::All)],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        indexer.init(run()).await.unwrap();
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (5..=10).collect::<Vec<_>>()
+        );
+
+        // Moving the start block earlier backfills only the newly covered
+        // range, without reindexing the blocks after it.
+        let mut indexer = Indexer::create(
+            indexer.eth,
+            indexer.database,
+            vec![event(3, config::Contract::All)],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        indexer.init(run()).await.unwrap();
+        assert_eq!(
+            indexer.database.block_numbers("Foo"),
+            (3..=10).collect::<Vec<_>>()
+        );
+        assert_eq!(indexer.eth.log_queries()[1..], [(3, 4)]);
+        assert_eq!(
+            indexer.database.event_block("Foo").await.unwrap().indexed,
+            10
+        );
+        assert_eq!(
+            indexer
+                .database
+                .event_filter("Foo")
+                .await
+                .unwrap()
+                .unwrap()
+                .start,
+            3
+        );
+
+        // Ending before the indexed block requires an explicit reindex.
+        let mut indexer = Indexer::create(
+            indexer.eth,
+            indexer.database,
+            vec![config::Event {
+                end: Some(8),
+                ..event(3, config::Contract::All)
+            }],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        assert!(indexer.init(run()).await.is_err());
+
+        // Changing the filter requires an explicit reindex.
+        let mut indexer = Indexer::create(
+            indexer.eth,
+            indexer.database,
+            vec![event(3, config::Contract::Address(Address([1; 20])))],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        assert!(indexer.init(run()).await.is_err());
+        indexer.reindex("Foo", 8).await.unwrap();
+        indexer.init(run()).await.unwrap();
+        assert_eq!(indexer.database.block_numbers("Foo"), [3, 4, 5, 6, 7]);
+    }
+
+    #[tokio::test]
+    async fn discovers_factory_contracts() {
+        let factory = Address([1; 20]);
+        let (first, second) = (Address([2; 20]), Address([3; 20]));
+        let created = |pool: Address| {
+            let mut data = vec![0; 32];
+            data[12..].copy_from_slice(&pool.0);
+            FakeLog {
+                address: factory,
+                topics: vec![keccak!("PoolCreated(address)")],
+                data,
+            }
+        };
+        let swap = |pool: Address| FakeLog::new(pool, &[keccak!("Swap()")]);
+
+        let node = fake::Node::new();
+        node.mine(vec![swap(first)]);
+        node.mine(vec![created(first), swap(first)]);
+        node.mine(vec![swap(first), swap(second)]);
+        node.mine(vec![created(second)]);
+        node.mine(vec![swap(second)]);
+        node.finalize(3);
+
+        let mut pool = config::Event::for_signature("event Swap()");
+        pool.contract = config::Contract::Factory(config::Factory {
+            from_event: "PoolCreated".to_string(),
+            field: "pool".to_string(),
+        });
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![
+                config::Event::for_signature("event PoolCreated(address pool)"),
+                pool,
+            ],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+
+        // Logs are only indexed from the block the contract was created in.
+        let finalized = indexer.init(run()).await.unwrap();
+        assert_eq!(indexer.database.block_numbers("Swap"), [2, 3]);
+
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(indexer.database.block_numbers("Swap"), [2, 3, 5]);
+
+        // Contracts created in reorged blocks are no longer indexed.
+        indexer.eth.reorg(4);
+        indexer.eth.mine(vec![]);
+        indexer.eth.mine(vec![swap(second)]);
+        indexer.eth.mine(vec![]);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(indexer.database.block_numbers("Swap"), [2, 3]);
+        assert_eq!(indexer.adapters[1].child(&second), None);
+
+        // Discovered contracts are loaded from the database on restart.
+        indexer.adapters[1].set_children([]);
+        indexer.init(run()).await.unwrap();
+        assert_eq!(indexer.adapters[1].child(&first), Some(2));
+    }
+
+    #[tokio::test]
+    async fn stores_undecoded_logs() {
+        // An ERC-721 transfer does not decode as an ERC-20 transfer.
+        let node = fake::Node::new();
+        node.mine(vec![FakeLog::new(
+            Address::default(),
+            &[
+                keccak!("Transfer(address,address,uint256)"),
+                Digest::default(),
+                Digest::default(),
+                Digest::default(),
+            ],
+        )]);
+        node.finalize(1);
+
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![config::Event::for_signature(
+                "event Transfer(address indexed from, address indexed to, uint256 value)",
+            )],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let config = Run {
+            fatal_decode_errors: true,
+            ..run()
+        };
+        assert!(indexer.init(config).await.is_err());
+
+        indexer.init(run()).await.unwrap();
+        assert_eq!(indexer.database.block_numbers("Transfer"), [] as [u64; 0]);
+        assert_eq!(indexer.database.block_numbers("_undecoded_logs"), [1]);
+    }
+
+    #[tokio::test]
+    async fn indexes_calls() {
+        let token = Address([1; 20]);
+        let transfer = |token: Address| {
+            let mut input = keccak!("transfer(address,uint256)").0[..4].to_vec();
+            input.extend([0; 12]);
+            input.extend([2; 20]);
+            input.extend([0; 32]);
+            FakeTransaction::call(Address([3; 20]), token, input)
+        };
+
+        let node = fake::Node::new();
+        node.mine_transactions(vec![transfer(token)]);
+        node.mine_transactions(vec![transfer(Address::default())]);
+        node.mine_transactions(vec![FakeTransaction {
+            success: false,
+            ..transfer(token)
+        }]);
+        node.finalize(2);
+        node.mine_transactions(vec![transfer(Address::default()), transfer(token)]);
+
+        let mut call = config::Call::for_signature("function transfer(address to, uint256 value)");
+        call.contract = config::Contract::Address(token);
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![],
+            vec![call],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+
+        let finalized = indexer.init(run()).await.unwrap();
+        assert_eq!(indexer.database.block_numbers("transfer"), [1]);
+
+        // Reverted calls are indexed as well.
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(indexer.database.block_numbers("transfer"), [1, 3, 4]);
+        assert_eq!(
+            indexer
+                .database
+                .event_block("transfer")
+                .await
+                .unwrap()
+                .indexed,
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn indexes_internal_calls_and_transfers() {
+        let token = Address([1; 20]);
+        let settlement = Address([4; 20]);
+        let mut input = keccak!("transfer(address,uint256)").0[..4].to_vec();
+        input.extend([0; 12]);
+        input.extend([2; 20]);
+        input.extend([0; 32]);
+
+        let node = fake::Node::new();
+        node.mine_transactions(vec![FakeTransaction {
+            calls: vec![
+                FakeTransaction::call(settlement, token, input.clone()),
+                FakeTransaction {
+                    value: U256::from(1_u64),
+                    ..FakeTransaction::call(settlement, Address([5; 20]), vec![])
+                },
+            ],
+            ..FakeTransaction::call(Address([3; 20]), settlement, vec![])
+        }]);
+        node.finalize(1);
+
+        let mut call = config::Call::for_signature("function transfer(address to, uint256 value)");
+        call.contract = config::Contract::Address(token);
+        let transfer = config::Transfer {
+            name: "eth_transfers".to_string(),
+            start: 0,
+            end: None,
+            contract: config::Contract::Address(settlement),
+        };
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![],
+            vec![call.clone()],
+            vec![transfer],
+            vec![],
+        )
+        .unwrap();
+
+        // Transfers need a trace source.
+        assert!(indexer.init(run()).await.is_err());
+
+        let config = Run {
+            traces: Some(Tracer::CallTracer),
+            ..run()
+        };
+        indexer.init(config).await.unwrap();
+        // Internal calls are only indexed when configured.
+        assert_eq!(indexer.database.block_numbers("transfer"), [] as [u64; 0]);
+        assert_eq!(indexer.database.block_numbers("eth_transfers"), [1]);
+
+        let node = fake::Node::new();
+        node.mine_transactions(vec![FakeTransaction {
+            calls: vec![FakeTransaction::call(settlement, token, input)],
+            ..FakeTransaction::call(Address([3; 20]), settlement, vec![])
+        }]);
+        node.finalize(1);
+        call.internal = true;
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![],
+            vec![call],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        indexer.init(config).await.unwrap();
+        assert_eq!(indexer.database.block_numbers("transfer"), [1]);
+    }
+
+    #[tokio::test]
+    async fn stores_transaction_receipts() {
+        let foo = || FakeLog::new(Address::default(), &[keccak!("Foo()")]);
+        let bar = || FakeLog::new(Address::default(), &[keccak!("Bar()")]);
+        let node = fake::Node::new();
+        node.mine(vec![foo(), bar(), foo()]);
+        node.mine(vec![bar()]);
+        node.finalize(1);
+        node.mine(vec![foo()]);
+
+        let mut foo = config::Event::for_signature("event Foo()");
+        foo.receipt = true;
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![foo, config::Event::for_signature("event Bar()")],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        // Only transactions with `Foo` logs have receipts.
+        assert_eq!(indexer.database.transactions(), [(1, 0), (1, 2), (3, 0)]);
+
+        // Receipts of reorged blocks are removed along with their logs.
+        indexer.eth.reorg(3);
+        indexer.eth.mine(vec![]);
+        indexer.eth.mine(vec![]);
+        assert!(indexer.sync(run(), &mut chain).await.unwrap());
+        assert_eq!(indexer.database.transactions(), [(1, 0), (1, 2)]);
+    }
+
+    #[tokio::test]
+    async fn takes_snapshots() {
+        let node = fake::Node::new();
+        for _ in 0..6 {
+            node.mine(vec![]);
+        }
+        node.finalize(4);
+
+        let snapshot = config::Snapshot {
+            name: "supplies".to_string(),
+            start: 1,
+            end: None,
+            contract: config::Contract::Addresses(vec![Address([1; 20]), Address::default()]),
+            signature: solabi::abi::FunctionDescriptor::parse_declaration(
+                "function totalSupply() returns (uint256)",
+            )
+            .unwrap(),
+            arguments: vec![],
+            interval: 2,
+        };
+        let mut indexer = Indexer::create(
+            node,
+            Sqlite::new_for_test(),
+            vec![],
+            vec![],
+            vec![],
+            vec![snapshot],
+        )
+        .unwrap();
+        let finalized = indexer.init(run()).await.unwrap();
+        // Calls to the zero address revert in the fake node, so there is only
+        // one snapshot per block.
+        assert_eq!(indexer.database.block_numbers("supplies"), [1, 3]);
+
+        let mut chain = Chain::new(finalized.number, finalized.hash);
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(indexer.database.block_numbers("supplies"), [1, 3, 5]);
+
+        // Snapshots of reorged blocks are replaced.
+        indexer.eth.reorg(5);
+        for _ in 0..3 {
+            indexer.eth.mine(vec![]);
+        }
+        while indexer.sync(run(), &mut chain).await.unwrap() {}
+        assert_eq!(indexer.database.block_numbers("supplies"), [1, 3, 5, 7]);
+        assert_eq!(
+            indexer
+                .database
+                .event_block("supplies")
+                .await
+                .unwrap()
+                .indexed,
+            7
+        );
+    }
 }
@@ -0,0 +1,681 @@
+//! Abstraction over the Ethereum node that the indexer reads blockchain data
+//! from. This allows the indexer to be driven by something other than an HTTP
+//! JSON RPC client, most notably a fake node for testing.
+
+use {
+    super::{hex, trace},
+    anyhow::{ensure, Result},
+    ethrpc::{
+        eth,
+        types::{BlockSpec, BlockTag, Digest, Hydrated, Log, LogFilter},
+    },
+    futures::{future::BoxFuture, stream, FutureExt, StreamExt, TryStreamExt},
+    serde::Deserialize,
+    solabi::ethprim::{Address, U256},
+};
+
+/// How many calls to execute at once when a batch of view calls has to be
+/// retried one call at a time.
+const VIEW_CALL_CONCURRENCY: usize = 10;
+
+/// The parts of a block header that the indexer cares about.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Header {
+    pub number: U256,
+    pub hash: Digest,
+    pub parent_hash: Digest,
+    pub timestamp: U256,
+}
+
+/// The parts of a transaction that the indexer cares about.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    pub hash: Digest,
+    #[serde(rename = "transactionIndex", with = "serialization::quantity")]
+    pub index: u64,
+    pub from: Address,
+    /// The called contract, or `None` for contract creations.
+    pub to: Option<Address>,
+    #[serde(with = "serialization::u256")]
+    pub value: U256,
+    #[serde(with = "serialization::bytes")]
+    pub input: Vec<u8>,
+}
+
+/// The parts of a transaction receipt that the indexer cares about.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+    pub transaction_hash: Digest,
+    #[serde(with = "serialization::quantity")]
+    pub transaction_index: u64,
+    /// The sender. This and the gas fields are only needed for storing
+    /// receipts, and are optional so that nodes that omit them can still be
+    /// used for the success status.
+    #[serde(default)]
+    pub from: Option<Address>,
+    /// The recipient, or `None` for contract creations.
+    #[serde(default)]
+    pub to: Option<Address>,
+    #[serde(default, with = "serialization::optional_quantity")]
+    pub gas_used: Option<u64>,
+    #[serde(default, with = "serialization::optional_quantity")]
+    pub effective_gas_price: Option<u64>,
+    /// Whether the transaction succeeded or reverted. Receipts from before the
+    /// Byzantium fork have a state root instead of a status, in which case
+    /// this is `None`.
+    #[serde(default, rename = "status", with = "serialization::optional_status")]
+    pub success: Option<bool>,
+}
+
+/// A call made in a transaction. This is either the transaction itself or,
+/// when tracing, an internal call made by a contract.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Frame {
+    pub transaction_hash: Digest,
+    pub transaction_index: u64,
+    /// The position of the frame among the frames of its block, in the order
+    /// the calls were made.
+    pub position: u64,
+    /// The call depth, 0 for the transaction itself.
+    pub depth: u64,
+    pub from: Address,
+    /// The called or created contract.
+    pub to: Option<Address>,
+    /// The transferred value. This is always 0 for delegate and static calls.
+    pub value: U256,
+    pub input: Vec<u8>,
+    /// Whether the call succeeded. Calls made by a reverted call are reverted
+    /// as well.
+    pub success: bool,
+}
+
+/// The node method used for tracing the calls of a block. It is configured as
+/// either `"call-tracer"` or `"parity"`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tracer {
+    /// `debug_traceBlockByHash` with Geth's built-in `callTracer`.
+    CallTracer,
+    /// The Parity-style `trace_block` method, as supported by Erigon,
+    /// Nethermind and Reth.
+    Parity,
+}
+
+/// A call to a view function of a contract at a specific block.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ViewCall {
+    pub to: Address,
+    pub input: Vec<u8>,
+    pub block: Digest,
+}
+
+/// Identifies a block to fetch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockId {
+    Number(U256),
+    Latest,
+    Safe,
+    Finalized,
+}
+
+/// An Ethereum node.
+pub trait Node: Send + Sync {
+    /// Fetches the headers for the specified blocks in a single batch. Blocks
+    /// that do not exist (yet) are returned as `None`.
+    fn headers<'a>(&'a self, blocks: &'a [BlockId]) -> BoxFuture<'a, Result<Vec<Option<Header>>>>;
+
+    /// Fetches the logs for the specified filters in a single batch.
+    fn logs(&self, filters: Vec<LogFilter>) -> BoxFuture<'_, Result<Vec<Vec<Log>>>>;
+
+    /// Fetches the transactions of the blocks with the specified hashes in a
+    /// single batch. Unknown blocks are returned as `None`.
+    fn transactions<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Transaction>>>>>;
+
+    /// Fetches the transaction receipts of the blocks with the specified
+    /// hashes in a single batch. Unknown blocks are returned as `None`.
+    fn receipts<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Receipt>>>>>;
+
+    /// Traces the calls of the specified blocks in a single batch, returning
+    /// the call frames of each block.
+    fn traces<'a>(
+        &'a self,
+        tracer: Tracer,
+        blocks: &'a [Header],
+    ) -> BoxFuture<'a, Result<Vec<Vec<Frame>>>>;
+
+    /// Executes the specified view calls, returning the return data of each
+    /// call or `None` if the call reverted. Calls are executed in a single
+    /// batch, unless one of them reverts.
+    fn view_calls<'a>(
+        &'a self,
+        calls: &'a [ViewCall],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<u8>>>>>;
+
+    /// Fetches the header for the specified block as agreed upon by the
+    /// endpoints backing the node. This is used for double checking blocks
+    /// that would cause a reorg. Nodes with a single endpoint just fetch the
+    /// header.
+    fn consensus(&self, number: U256) -> BoxFuture<'_, Result<Option<Header>>> {
+        async move {
+            Ok(self
+                .headers(&[BlockId::Number(number)])
+                .await?
+                .into_iter()
+                .next()
+                .flatten())
+        }
+        .boxed()
+    }
+}
+
+impl Node for ethrpc::http::Client {
+    fn headers<'a>(&'a self, blocks: &'a [BlockId]) -> BoxFuture<'a, Result<Vec<Option<Header>>>> {
+        async move {
+            if blocks.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let blocks = self
+                .batch(
+                    blocks
+                        .iter()
+                        .map(|block| (eth::GetBlockByNumber, (block.spec(), Hydrated::No)))
+                        .collect::<Vec<_>>(),
+                )
+                .await?;
+            Ok(blocks
+                .into_iter()
+                .map(|block| {
+                    block.map(|block| Header {
+                        number: block.number,
+                        hash: block.hash,
+                        parent_hash: block.parent_hash,
+                        timestamp: block.timestamp,
+                    })
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn logs(&self, filters: Vec<LogFilter>) -> BoxFuture<'_, Result<Vec<Vec<Log>>>> {
+        async move {
+            if filters.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let logs = self
+                .batch(
+                    filters
+                        .into_iter()
+                        .map(|filter| (eth::GetLogs, (filter,)))
+                        .collect::<Vec<_>>(),
+                )
+                .await?;
+            Ok(logs)
+        }
+        .boxed()
+    }
+
+    fn transactions<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Transaction>>>>> {
+        async move {
+            if blocks.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let blocks = self
+                .batch(
+                    blocks
+                        .iter()
+                        .map(|block| (raw::GetBlockByHash, (*block, Hydrated::Yes)))
+                        .collect::<Vec<_>>(),
+                )
+                .await?;
+            Ok(blocks
+                .into_iter()
+                .map(|block| block.map(|block| block.transactions))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn receipts<'a>(
+        &'a self,
+        blocks: &'a [Digest],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<Receipt>>>>> {
+        async move {
+            if blocks.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let receipts = self
+                .batch(
+                    blocks
+                        .iter()
+                        .map(|block| (raw::GetBlockReceipts, (*block,)))
+                        .collect::<Vec<_>>(),
+                )
+                .await?;
+            Ok(receipts)
+        }
+        .boxed()
+    }
+
+    fn traces<'a>(
+        &'a self,
+        tracer: Tracer,
+        blocks: &'a [Header],
+    ) -> BoxFuture<'a, Result<Vec<Vec<Frame>>>> {
+        async move {
+            if blocks.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            match tracer {
+                Tracer::CallTracer => {
+                    let options = serde_json::json!({ "tracer": "callTracer" });
+                    let traces = self
+                        .batch(
+                            blocks
+                                .iter()
+                                .map(|block| (raw::TraceBlockByHash, (block.hash, options.clone())))
+                                .collect::<Vec<_>>(),
+                        )
+                        .await?;
+                    Ok(traces.into_iter().map(trace::call_tracer).collect())
+                }
+                Tracer::Parity => {
+                    let traces = self
+                        .batch(
+                            blocks
+                                .iter()
+                                .map(|block| (raw::TraceBlock, (BlockSpec::from(block.number),)))
+                                .collect::<Vec<_>>(),
+                        )
+                        .await?;
+
+                    // Parity-style traces can only be requested by block
+                    // number, so make sure that they are for the right block.
+                    let mut frames = Vec::new();
+                    for (block, traces) in blocks.iter().zip(traces) {
+                        ensure!(
+                            traces.iter().all(|trace| trace.block_hash == block.hash),
+                            "traces for block {} do not match its hash",
+                            block.number
+                        );
+                        frames.push(trace::parity(traces));
+                    }
+                    Ok(frames)
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn view_calls<'a>(
+        &'a self,
+        calls: &'a [ViewCall],
+    ) -> BoxFuture<'a, Result<Vec<Option<Vec<u8>>>>> {
+        async move {
+            if calls.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            // Calls are executed at the block with the specified hash, so they
+            // are guaranteed to use the state of that block.
+            let params = |call: &ViewCall| {
+                (
+                    serde_json::json!({ "to": call.to, "data": hex::encode(&call.input) }),
+                    serde_json::json!({ "blockHash": call.block }),
+                )
+            };
+
+            let result = self
+                .batch(
+                    calls
+                        .iter()
+                        .map(|call| (raw::Call, params(call)))
+                        .collect::<Vec<_>>(),
+                )
+                .await
+                .map_err(anyhow::Error::from);
+            match result {
+                Ok(results) => Ok(results.into_iter().map(|data| Some(data.0)).collect()),
+                // A single reverting call fails the whole batch, so fall back
+                // to executing the calls one by one.
+                Err(err) if is_revert_error(&err) => {
+                    stream::iter(calls)
+                        .map(|call| async move {
+                            match self.call(raw::Call, params(call)).await {
+                                Ok(data) => Ok(Some(data.0)),
+                                Err(err) => {
+                                    let err = anyhow::Error::from(err);
+                                    if is_revert_error(&err) {
+                                        Ok(None)
+                                    } else {
+                                        Err(err)
+                                    }
+                                }
+                            }
+                        })
+                        .buffered(VIEW_CALL_CONCURRENCY)
+                        .try_collect()
+                        .await
+                }
+                Err(err) => Err(err),
+            }
+        }
+        .boxed()
+    }
+}
+
+ethrpc::module! {
+    /// Block methods that decode into the indexer's own transaction and
+    /// receipt types, which only contain the fields the indexer cares about.
+    mod raw {
+        /// Returns a block with its hydrated transactions by hash.
+        pub struct GetBlockByHash as "eth_getBlockByHash"
+            (Digest, Hydrated) => Option<super::TransactionBlock>;
+
+        /// Returns all transaction receipts of a block.
+        pub struct GetBlockReceipts as "eth_getBlockReceipts"
+            (Digest,) => Option<Vec<super::Receipt>>;
+
+        /// Traces all transactions of a block by hash with the specified
+        /// tracer options.
+        pub struct TraceBlockByHash as "debug_traceBlockByHash"
+            (Digest, serde_json::Value) => Vec<super::trace::TransactionTrace>;
+
+        /// Returns Parity-style traces for all transactions of a block.
+        pub struct TraceBlock as "trace_block"
+            (BlockSpec,) => Vec<super::trace::Trace>;
+
+        /// Executes a call with the specified transaction and block
+        /// parameters, returning its return data.
+        pub struct Call as "eth_call"
+            (serde_json::Value, serde_json::Value) => super::ReturnData;
+    }
+}
+
+/// The return data of a call.
+#[derive(Deserialize)]
+struct ReturnData(#[serde(with = "serialization::bytes")] Vec<u8>);
+
+/// A block with hydrated transactions.
+#[derive(Deserialize)]
+struct TransactionBlock {
+    transactions: Vec<Transaction>,
+}
+
+impl BlockId {
+    fn spec(&self) -> BlockSpec {
+        match self {
+            Self::Number(number) => (*number).into(),
+            Self::Latest => BlockTag::Latest.into(),
+            Self::Safe => BlockTag::Safe.into(),
+            Self::Finalized => BlockTag::Finalized.into(),
+        }
+    }
+}
+
+/// Returns `true` if the error indicates that an `eth_getLogs` request was
+/// rejected because its block range was too large or would return too many
+/// logs. Nodes and hosted RPC providers don't use a standard error code for
+/// this, so we match on the known error messages.
+pub fn is_log_range_error(err: &anyhow::Error) -> bool {
+    const MESSAGES: &[&str] = &[
+        // Geth, Erigon and Infura.
+        "query returned more than",
+        // Alchemy.
+        "log response size exceeded",
+        // BSC and Ankr.
+        "response size should not greater than",
+        "exceed maximum block range",
+        "block range is too wide",
+        // Alchemy and QuickNode.
+        "eth_getlogs is limited to",
+        // Chainstack.
+        "block range limit exceeded",
+        // Coinbase and Cloudflare.
+        "block range too large",
+        "block range is too large",
+        // Llama RPC.
+        "query exceeds max results",
+    ];
+
+    let err = format!("{err:?}").to_lowercase();
+    MESSAGES.iter().any(|message| err.contains(message))
+}
+
+/// Returns `true` if the error indicates that an `eth_call` reverted. Like
+/// log range errors, these are matched by their error messages.
+pub fn is_revert_error(err: &anyhow::Error) -> bool {
+    const MESSAGES: &[&str] = &[
+        // Geth, Erigon, Reth and most hosted providers.
+        "execution reverted",
+        "invalid opcode",
+        // Nethermind and OpenEthereum.
+        "vm execution error",
+        // Hardhat and Ganache.
+        "vm exception while processing transaction",
+    ];
+
+    let err = format!("{err:?}").to_lowercase();
+    MESSAGES.iter().any(|message| err.contains(message))
+}
+
+/// Serialization helpers for JSON RPC values.
+pub mod serialization {
+    pub mod quantity {
+        use serde::{de, Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s = String::deserialize(deserializer)?;
+            let digits = s
+                .strip_prefix("0x")
+                .ok_or_else(|| de::Error::custom("expected 0x-prefixed quantity"))?;
+            u64::from_str_radix(digits, 16).map_err(de::Error::custom)
+        }
+    }
+
+    pub mod optional_quantity {
+        use serde::{Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            #[derive(Deserialize)]
+            struct Quantity(#[serde(with = "super::quantity")] u64);
+
+            let quantity = Option::<Quantity>::deserialize(deserializer)?;
+            Ok(quantity.map(|Quantity(value)| value))
+        }
+    }
+
+    pub mod u256 {
+        use {
+            serde::{de, Deserialize, Deserializer},
+            solabi::ethprim::U256,
+        };
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<U256, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s = String::deserialize(deserializer)?;
+            let digits = s
+                .strip_prefix("0x")
+                .ok_or_else(|| de::Error::custom("expected 0x-prefixed quantity"))?;
+            U256::from_str_radix(digits, 16).map_err(de::Error::custom)
+        }
+    }
+
+    pub mod optional_status {
+        use serde::{de, Deserializer};
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            match super::optional_quantity::deserialize(deserializer)? {
+                None => Ok(None),
+                Some(0) => Ok(Some(false)),
+                Some(1) => Ok(Some(true)),
+                Some(status) => Err(de::Error::custom(format!("invalid status {status}"))),
+            }
+        }
+    }
+
+    pub mod bytes {
+        use {
+            crate::indexer::hex,
+            serde::{de, Deserialize, Deserializer},
+        };
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s = String::deserialize(deserializer)?;
+            hex::decode(&s).map_err(de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_range_errors() {
+        for message in [
+            "query returned more than 10000 results",
+            "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block \
+             range and no limit on the response size",
+            "block range is too wide",
+            "exceed maximum block range: 5000",
+            "eth_getLogs is limited to a 10,000 range",
+        ] {
+            assert!(is_log_range_error(&anyhow::anyhow!("{message}")));
+        }
+        for message in [
+            "connection refused",
+            "requests are limited to 10 per second",
+            "header not found for block range start",
+            "max results per batch is 100",
+        ] {
+            assert!(!is_log_range_error(&anyhow::anyhow!("{message}")));
+        }
+    }
+
+    #[test]
+    fn revert_errors() {
+        for message in [
+            "execution reverted",
+            "execution reverted: Ownable: caller is not the owner",
+            "invalid opcode: INVALID",
+            "VM execution error.",
+            "VM Exception while processing transaction: revert",
+        ] {
+            assert!(is_revert_error(&anyhow::anyhow!("{message}")));
+        }
+        for message in [
+            "connection refused",
+            "header not found",
+            "revert reason decoding is not supported",
+        ] {
+            assert!(!is_revert_error(&anyhow::anyhow!("{message}")));
+        }
+    }
+
+    #[test]
+    fn deserializes_transactions() {
+        let transaction = serde_json::from_value::<Transaction>(serde_json::json!({
+            "hash": format!("0x{}", "11".repeat(32)),
+            "transactionIndex": "0x2a",
+            "from": format!("0x{}", "22".repeat(20)),
+            "to": null,
+            "value": "0xde0b6b3a7640000",
+            "input": "0x13d79a0b",
+            "gas": "0x5208",
+        }))
+        .unwrap();
+        assert_eq!(
+            transaction,
+            Transaction {
+                hash: Digest([0x11; 32]),
+                index: 42,
+                from: Address([0x22; 20]),
+                to: None,
+                value: U256::from(10_u64.pow(18)),
+                input: vec![0x13, 0xd7, 0x9a, 0x0b],
+            }
+        );
+
+        let receipt = serde_json::from_value::<Receipt>(serde_json::json!({
+            "transactionHash": format!("0x{}", "11".repeat(32)),
+            "transactionIndex": "0x2a",
+            "from": format!("0x{}", "22".repeat(20)),
+            "to": format!("0x{}", "33".repeat(20)),
+            "gasUsed": "0x5208",
+            "effectiveGasPrice": "0x3b9aca00",
+            "cumulativeGasUsed": "0x5208",
+            "status": "0x0",
+        }))
+        .unwrap();
+        assert_eq!(
+            receipt,
+            Receipt {
+                transaction_hash: Digest([0x11; 32]),
+                transaction_index: 42,
+                from: Some(Address([0x22; 20])),
+                to: Some(Address([0x33; 20])),
+                gas_used: Some(21_000),
+                effective_gas_price: Some(1_000_000_000),
+                success: Some(false),
+            }
+        );
+
+        // Only the fields needed for the success status are required.
+        let receipt = serde_json::from_value::<Receipt>(serde_json::json!({
+            "transactionHash": format!("0x{}", "11".repeat(32)),
+            "transactionIndex": "0x2a",
+            "status": "0x1",
+        }))
+        .unwrap();
+        assert_eq!(
+            receipt,
+            Receipt {
+                transaction_hash: Digest([0x11; 32]),
+                transaction_index: 42,
+                success: Some(true),
+                ..Default::default()
+            }
+        );
+
+        // Receipts from before Byzantium have a state root instead of a
+        // status.
+        let receipt = serde_json::from_value::<Receipt>(serde_json::json!({
+            "transactionHash": format!("0x{}", "11".repeat(32)),
+            "transactionIndex": "0x2a",
+            "root": format!("0x{}", "44".repeat(32)),
+        }))
+        .unwrap();
+        assert_eq!(receipt.success, None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Retrying of transient failures. Errors from the node and the database are
+//! classified as either transient, in which case the failed operation is
+//! retried with jittered exponential backoff, or fatal.
+
+use {
+    anyhow::{Error, Result},
+    std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+        time::Duration,
+    },
+    tokio::time,
+};
+
+/// A retry policy.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// The backoff before the first retry.
+    pub initial_backoff: Duration,
+    /// The maximum backoff between retries.
+    pub max_backoff: Duration,
+    /// The maximum number of consecutive retries before giving up. Retries
+    /// forever if `None`.
+    pub max_retries: Option<u32>,
+}
+
+impl Retry {
+    /// Returns the backoff for the specified (zero-based) retry attempt, or
+    /// `None` if the retry policy has been exhausted.
+    fn backoff(&self, attempt: u32) -> Option<Duration> {
+        if self.max_retries.is_some_and(|max| attempt >= max) {
+            return None;
+        }
+
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        Some(backoff.mul_f64(0.5 + jitter() / 2.))
+    }
+}
+
+/// Tracks consecutive failures of an operation.
+pub struct Backoff {
+    policy: Retry,
+    attempt: u32,
+}
+
+impl Backoff {
+    /// Creates a new backoff for the specified policy.
+    pub fn new(policy: Retry) -> Self {
+        Self { policy, attempt: 0 }
+    }
+
+    /// Resets the backoff after the operation succeeded.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Handles a failure of the operation. For transient errors, this waits
+    /// before the operation should be retried. Fatal errors, or transient
+    /// errors once the retry policy is exhausted, are returned.
+    pub async fn failed(&mut self, err: Error) -> Result<()> {
+        if !is_transient(&err) {
+            return Err(err);
+        }
+        let Some(backoff) = self.policy.backoff(self.attempt) else {
+            return Err(err.context(format!("giving up after {} retries", self.attempt)));
+        };
+
+        self.attempt += 1;
+        tracing::warn!(
+            ?err, attempt = %self.attempt, ?backoff,
+            "transient error; retrying"
+        );
+        time::sleep(backoff).await;
+        Ok(())
+    }
+}
+
+/// Returns whether or not an error is transient, meaning that the failing
+/// operation may succeed if it is retried.
+pub fn is_transient(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<ethrpc::http::Error>() {
+            // Transport errors and timeouts, rate limiting and server errors
+            // are transient. Errors returned by the node as JSON RPC error
+            // objects, or responses that fail to deserialize, are not.
+            use ethrpc::http::Error;
+            return match err {
+                Error::Http(_) => true,
+                Error::Status(status, _) => status.as_u16() == 429 || status.is_server_error(),
+                _ => false,
+            };
+        }
+        if cause.is::<std::io::Error>() {
+            return true;
+        }
+        if let Some(err) = cause.downcast_ref::<rusqlite::Error>() {
+            return matches!(
+                err.sqlite_error_code(),
+                Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+            );
+        }
+        if let Some(err) = cause.downcast_ref::<tokio_postgres::Error>() {
+            // A closed client can't be used anymore, so there is no point in
+            // retrying. Errors without an SQL state are I/O errors or timeouts.
+            use tokio_postgres::error::SqlState;
+            return !err.is_closed()
+                && match err.code() {
+                    Some(code) => {
+                        *code == SqlState::T_R_SERIALIZATION_FAILURE
+                            || *code == SqlState::T_R_DEADLOCK_DETECTED
+                    }
+                    None => true,
+                };
+        }
+        false
+    })
+}
+
+/// Returns a random number in the range `[0, 1]`.
+fn jitter() -> f64 {
+    // `RandomState` is randomly seeded for every instance, which is good
+    // enough for jitter and saves us from pulling in a random number crate.
+    let random = RandomState::new().build_hasher().finish();
+    random as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let retry = Retry {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            max_retries: Some(5),
+        };
+
+        for (attempt, max) in [1, 2, 4, 8, 10].into_iter().enumerate() {
+            let backoff = retry.backoff(attempt as _).unwrap();
+            let max = Duration::from_secs(max);
+            assert!(backoff >= max / 2 && backoff <= max);
+        }
+        assert_eq!(retry.backoff(5), None);
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let io = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(is_transient(&Error::from(io).context("fetch logs")));
+
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert!(is_transient(&Error::from(busy).context("commit")));
+
+        assert!(!is_transient(&anyhow::anyhow!(
+            "reorg past finalized block"
+        )));
+        let json = serde_json::from_str::<u64>("{}").unwrap_err();
+        assert!(!is_transient(
+            &Error::from(ethrpc::http::Error::from(json)).context("fetch logs")
+        ));
+        assert!(!is_transient(
+            &Error::from(rusqlite::Error::InvalidQuery).context("prepare")
+        ));
+    }
+}
@@ -0,0 +1,352 @@
+//! An adapter for periodic snapshots of a view function. The function is
+//! called on each of the configured contracts every `interval` blocks, and its
+//! return values are decoded into the fields of a synthetic event.
+//!
+//! Snapshots are stored like event logs, with the position of the contract in
+//! the contract list as log index. This way they are mapped to tables and
+//! removed on reorgs the same way as events.
+
+use {
+    super::{
+        filter, hex,
+        node::{Header, ViewCall},
+    },
+    crate::{config, database},
+    anyhow::{bail, ensure, Context, Result},
+    solabi::{
+        abi::{EventDescriptor, EventField},
+        ethprim::Address,
+        value::{FunctionEncoder, Value},
+    },
+    std::{cmp, collections::HashMap},
+};
+
+/// An adapter for periodic snapshots of a single view function.
+pub struct SnapshotAdapter {
+    name: String,
+    start: u64,
+    /// The last block to take snapshots at, or `None` to take them
+    /// indefinitely.
+    end: Option<u64>,
+    interval: u64,
+    /// The contracts to call, in the order of their log index.
+    contracts: Vec<Address>,
+    labels: HashMap<Address, String>,
+    /// The encoded calldata, which is the same for every call.
+    input: Vec<u8>,
+    encoder: FunctionEncoder,
+    /// The event signature that snapshots are stored with.
+    signature: EventDescriptor,
+}
+
+/// A snapshot of a single contract at a block.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// The index of the adapter that the snapshot is for.
+    pub adapter: usize,
+    pub header: Header,
+    /// The position of the contract in the adapter's contract list.
+    pub contract: usize,
+    /// The return data of the call, or `None` if it reverted or was not made
+    /// yet.
+    pub data: Option<Vec<u8>>,
+}
+
+impl SnapshotAdapter {
+    /// Creates a new adapter for snapshots of a view function.
+    pub fn new(config: config::Snapshot) -> Result<Self> {
+        ensure!(config.interval > 0, "snapshot interval must be positive");
+
+        let mut labels = HashMap::new();
+        let contracts = match config.contract {
+            config::Contract::All | config::Contract::Factory(_) => {
+                bail!("snapshots require contract addresses")
+            }
+            contract => contract
+                .addresses(&mut labels)?
+                .expect("contract addresses"),
+        };
+
+        let inputs = &config.signature.inputs;
+        ensure!(
+            config.arguments.len() == inputs.len(),
+            "expected {} arguments but got {}",
+            inputs.len(),
+            config.arguments.len()
+        );
+        let arguments = inputs
+            .iter()
+            .zip(&config.arguments)
+            .map(|(input, argument)| {
+                filter::value(&input.kind, argument)
+                    .with_context(|| format!("invalid argument '{}'", input.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let encoder = FunctionEncoder::new(&config.signature)?;
+        let input = encoder.encode_params(&arguments)?;
+
+        // Return values are often unnamed, but table columns need names.
+        let outputs = &config.signature.outputs;
+        let mut signature =
+            EventDescriptor::parse_declaration("event Snapshot()").expect("invalid snapshot");
+        signature.name = config.signature.name.clone();
+        signature.inputs = outputs
+            .iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let mut field = output.clone();
+                if field.name.is_empty() {
+                    field.name = match outputs.len() {
+                        1 => "value".to_string(),
+                        _ => format!("value{index}"),
+                    };
+                }
+                EventField {
+                    field,
+                    indexed: false,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            name: config.name,
+            start: config.start,
+            end: config.end,
+            interval: config.interval,
+            contracts,
+            labels,
+            input,
+            encoder,
+            signature,
+        })
+    }
+
+    /// Returns the name of the snapshot indexer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the event signature that snapshots are stored with. Its fields
+    /// are the function's return values.
+    pub fn signature(&self) -> &EventDescriptor {
+        &self.signature
+    }
+
+    /// Returns the first block to take snapshots at.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the last block to take snapshots at, if any.
+    pub fn end(&self) -> Option<u64> {
+        self.end
+    }
+
+    /// Returns a description of the contracts, calldata and interval of the
+    /// snapshots. The order of the contracts matters, since it determines the
+    /// log index that snapshots are stored with.
+    pub fn fingerprint(&self) -> String {
+        let contracts = self
+            .contracts
+            .iter()
+            .map(Address::to_string)
+            .collect::<Vec<_>>();
+        format!(
+            "contract = {}; input = {}; interval = {}",
+            contracts.join(", "),
+            hex::encode(&self.input),
+            self.interval
+        )
+    }
+
+    /// Returns the blocks to take snapshots at in an inclusive block range.
+    pub fn blocks(&self, from: u64, to: u64) -> impl Iterator<Item = u64> {
+        let from = cmp::max(from, self.start);
+        let to = cmp::min(to, self.end.unwrap_or(u64::MAX));
+        let first = self.start + (from - self.start).div_ceil(self.interval) * self.interval;
+        (first..=to).step_by(self.interval as usize)
+    }
+
+    /// Returns the view call for a snapshot.
+    pub fn call(&self, snapshot: &Snapshot) -> ViewCall {
+        ViewCall {
+            to: self.contracts[snapshot.contract],
+            input: self.input.clone(),
+            block: snapshot.header.hash,
+        }
+    }
+
+    /// Decodes the return data of a snapshot call into the fields of the
+    /// adapter's event signature.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<Value>> {
+        Ok(self.encoder.decode_returns(data)?)
+    }
+}
+
+/// Returns the snapshots to take at a block for each adapter. `ranges`
+/// contains the inclusive block ranges to take snapshots in for each adapter.
+pub fn schedule(
+    adapters: &[SnapshotAdapter],
+    ranges: &[(usize, u64, u64)],
+    header: &Header,
+) -> Vec<Snapshot> {
+    let number = header.number.as_u64();
+    let mut snapshots = Vec::new();
+    for &(adapter, from, to) in ranges {
+        if adapters[adapter].blocks(number, number).next().is_none()
+            || !(from..=to).contains(&number)
+        {
+            continue;
+        }
+        snapshots.extend(
+            (0..adapters[adapter].contracts.len()).map(|contract| Snapshot {
+                adapter,
+                header: *header,
+                contract,
+                data: None,
+            }),
+        );
+    }
+    snapshots
+}
+
+/// Converts snapshots to database logs. Snapshots of reverted calls are
+/// skipped. Snapshots that fail to decode are skipped as well, unless decoding
+/// errors are fatal.
+pub fn database_logs(
+    adapters: &[SnapshotAdapter],
+    snapshots: Vec<Snapshot>,
+    fatal_decode_errors: bool,
+) -> Result<Vec<database::Log<'_>>> {
+    let mut logs = Vec::new();
+    for snapshot in snapshots {
+        let adapter = &adapters[snapshot.adapter];
+        let address = adapter.contracts[snapshot.contract];
+        let Some(data) = &snapshot.data else {
+            tracing::debug!(
+                snapshot = %adapter.name(), block = %snapshot.header.number, %address,
+                "snapshot call reverted"
+            );
+            continue;
+        };
+        let fields = match adapter.decode(data) {
+            Ok(fields) => fields,
+            Err(err) if fatal_decode_errors => {
+                return Err(err.context(format!(
+                    "failed to decode snapshot {} of {address} at block {}",
+                    adapter.name(),
+                    snapshot.header.number
+                )));
+            }
+            Err(err) => {
+                tracing::warn!(?err, ?snapshot, "failed to decode snapshot");
+                continue;
+            }
+        };
+
+        logs.push(database::Log {
+            event: adapter.name(),
+            block_number: snapshot.header.number.as_u64(),
+            block_timestamp: snapshot.header.timestamp.as_u64(),
+            block_hash: snapshot.header.hash,
+            log_index: snapshot.contract as u64,
+            address,
+            contract_label: adapter.labels.get(&address).map(String::as_str),
+            fields,
+            ..Default::default()
+        });
+    }
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solabi::{
+            abi::FunctionDescriptor,
+            ethprim::{address, U256},
+            value::Uint,
+        },
+    };
+
+    fn snapshot(signature: &str, arguments: &str, interval: u64) -> config::Snapshot {
+        config::Snapshot {
+            name: "snapshot".to_string(),
+            start: 10,
+            end: None,
+            contract: config::Contract::Addresses(vec![Address([1; 20]), Address([2; 20])]),
+            signature: FunctionDescriptor::parse_declaration(signature).unwrap(),
+            arguments: toml::from_str::<toml::Table>(&format!("arguments = {arguments}")).unwrap()
+                ["arguments"]
+                .as_array()
+                .unwrap()
+                .clone(),
+            interval,
+        }
+    }
+
+    #[test]
+    fn samples_blocks() {
+        let adapter = SnapshotAdapter::new(snapshot(
+            "function totalSupply() returns (uint256)",
+            "[]",
+            5,
+        ))
+        .unwrap();
+        assert_eq!(
+            adapter.blocks(0, 30).collect::<Vec<_>>(),
+            [10, 15, 20, 25, 30]
+        );
+        assert_eq!(adapter.blocks(16, 24).collect::<Vec<_>>(), [20]);
+        assert_eq!(adapter.blocks(16, 19).count(), 0);
+        assert_eq!(adapter.blocks(25, 25).collect::<Vec<_>>(), [25]);
+    }
+
+    #[test]
+    fn encodes_arguments_and_decodes_returns() {
+        let adapter = SnapshotAdapter::new(snapshot(
+            "function balanceOf(address owner) returns (uint256)",
+            r#"["0x9008D19f58AAbD9eD0D60971565AA8510560ab41"]"#,
+            1,
+        ))
+        .unwrap();
+
+        let mut input = hex_literal::hex!("70a08231").to_vec();
+        input.extend([0; 12]);
+        input.extend(address!("0x9008D19f58AAbD9eD0D60971565AA8510560ab41").0);
+        assert_eq!(adapter.input, input);
+
+        assert_eq!(
+            adapter
+                .signature()
+                .inputs
+                .iter()
+                .map(|input| input.field.name.as_str())
+                .collect::<Vec<_>>(),
+            ["value"]
+        );
+        assert_eq!(
+            adapter.decode(&U256::from(42_u64).to_be_bytes()).unwrap(),
+            [Value::Uint(Uint::new(256, U256::from(42_u64)).unwrap())]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for arguments in ["[]", "[true]", r#"["0x01"]"#] {
+            assert!(SnapshotAdapter::new(snapshot(
+                "function balanceOf(address owner) returns (uint256)",
+                arguments,
+                1,
+            ))
+            .is_err());
+        }
+        assert!(SnapshotAdapter::new(snapshot(
+            "function totalSupply() returns (uint256)",
+            "[]",
+            0
+        ))
+        .is_err());
+    }
+}
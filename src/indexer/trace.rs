@@ -0,0 +1,246 @@
+//! Conversion of block traces into call frames. Traces are either produced by
+//! Geth's `callTracer`, which reports a tree of calls per transaction, or by
+//! the Parity-style `trace_block` method, which reports a flat list of calls
+//! with their position in the call tree.
+
+use {
+    super::node::{serialization, Frame},
+    ethrpc::types::Digest,
+    serde::Deserialize,
+    solabi::ethprim::{Address, U256},
+};
+
+/// The `callTracer` trace of a single transaction.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionTrace {
+    tx_hash: Digest,
+    result: CallFrame,
+}
+
+/// A call reported by the `callTracer`, along with the calls it made.
+#[derive(Debug, Deserialize)]
+struct CallFrame {
+    #[serde(rename = "type")]
+    kind: String,
+    from: Address,
+    #[serde(default)]
+    to: Option<Address>,
+    #[serde(default, with = "serialization::u256")]
+    value: U256,
+    #[serde(default, with = "serialization::bytes")]
+    input: Vec<u8>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    calls: Vec<CallFrame>,
+}
+
+/// A single Parity-style trace.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trace {
+    #[serde(rename = "type")]
+    kind: String,
+    action: Action,
+    #[serde(default)]
+    result: Option<TraceResult>,
+    #[serde(default)]
+    error: Option<String>,
+    trace_address: Vec<u64>,
+    /// The transaction hash, or `None` for block rewards.
+    transaction_hash: Option<Digest>,
+    transaction_position: Option<u64>,
+    pub block_hash: Digest,
+}
+
+/// The action of a Parity-style trace. The fields depend on the trace type.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Action {
+    call_type: Option<String>,
+    from: Option<Address>,
+    to: Option<Address>,
+    #[serde(default, with = "serialization::u256")]
+    value: U256,
+    #[serde(default, with = "serialization::bytes")]
+    input: Vec<u8>,
+    #[serde(default, with = "serialization::bytes")]
+    init: Vec<u8>,
+    address: Option<Address>,
+    refund_address: Option<Address>,
+    #[serde(default, with = "serialization::u256")]
+    balance: U256,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceResult {
+    address: Option<Address>,
+}
+
+/// Converts the `callTracer` traces of a block's transactions into call
+/// frames. Calls are visited depth first, which is the order they were made
+/// in.
+pub fn call_tracer(traces: Vec<TransactionTrace>) -> Vec<Frame> {
+    fn visit(
+        frames: &mut Vec<Frame>,
+        transaction: (Digest, u64),
+        call: CallFrame,
+        depth: u64,
+        parent: bool,
+    ) {
+        let success = parent && call.error.is_none();
+        let value = match call.kind.as_str() {
+            "DELEGATECALL" | "STATICCALL" => U256::ZERO,
+            _ => call.value,
+        };
+        frames.push(Frame {
+            transaction_hash: transaction.0,
+            transaction_index: transaction.1,
+            position: frames.len() as u64,
+            depth,
+            from: call.from,
+            to: call.to,
+            value,
+            input: call.input,
+            success,
+        });
+        for child in call.calls {
+            visit(frames, transaction, child, depth + 1, success);
+        }
+    }
+
+    let mut frames = Vec::new();
+    for (index, trace) in traces.into_iter().enumerate() {
+        visit(
+            &mut frames,
+            (trace.tx_hash, index as u64),
+            trace.result,
+            0,
+            true,
+        );
+    }
+    frames
+}
+
+/// Converts the Parity-style traces of a block into call frames. Block and
+/// uncle rewards are not calls and are skipped.
+pub fn parity(traces: Vec<Trace>) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    // The success of the calls along the path to the current trace, indexed
+    // by depth. Traces are ordered depth first, so parents come first.
+    let mut path = Vec::<bool>::new();
+    for trace in traces {
+        let (Some(hash), Some(index)) = (trace.transaction_hash, trace.transaction_position) else {
+            continue;
+        };
+
+        let depth = trace.trace_address.len();
+        path.truncate(depth);
+        let success = trace.error.is_none() && path.last().copied().unwrap_or(true);
+        path.push(success);
+
+        let action = trace.action;
+        let (from, to, value, input) = match trace.kind.as_str() {
+            "create" => (
+                action.from,
+                trace.result.and_then(|result| result.address),
+                action.value,
+                action.init,
+            ),
+            "suicide" => (
+                action.address,
+                action.refund_address,
+                action.balance,
+                Vec::new(),
+            ),
+            _ => {
+                let value = match action.call_type.as_deref() {
+                    Some("delegatecall" | "staticcall") => U256::ZERO,
+                    _ => action.value,
+                };
+                (action.from, action.to, value, action.input)
+            }
+        };
+        frames.push(Frame {
+            transaction_hash: hash,
+            transaction_index: index,
+            position: frames.len() as u64,
+            depth: depth as u64,
+            from: from.unwrap_or_default(),
+            to,
+            value,
+            input,
+            success,
+        });
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solabi::ethprim::address};
+
+    /// The frames of the fixture block, which is the same for both tracers: a
+    /// settlement that pulls tokens with a `transferFrom`, calls a library
+    /// with `DELEGATECALL`, catches a reverted call and pays out ETH, followed
+    /// by a plain ETH transfer.
+    fn expected() -> Vec<(u64, u64, Address, bool, U256)> {
+        let settlement = address!("0x9008D19f58AAbD9eD0D60971565AA8510560ab41");
+        let weth = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let library = address!("0x2c4c28DDBdAc9C5E7055b4C863b72eA0149D8aFE");
+        let pool = address!("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+        let receiver = address!("0x40A50cf069e992AA4536211B23F286eF88752187");
+        let ether = U256::from(10_u64.pow(18));
+        vec![
+            (0, 0, settlement, true, U256::ZERO),
+            (0, 1, weth, true, U256::ZERO),
+            (0, 1, library, true, U256::ZERO),
+            (0, 1, pool, false, U256::ZERO),
+            (0, 2, weth, false, U256::ZERO),
+            (0, 1, receiver, true, ether),
+            (1, 0, receiver, true, ether / 2),
+        ]
+    }
+
+    fn summary(frames: &[Frame]) -> Vec<(u64, u64, Address, bool, U256)> {
+        frames
+            .iter()
+            .enumerate()
+            .map(|(position, frame)| {
+                assert_eq!(frame.position, position as u64);
+                (
+                    frame.transaction_index,
+                    frame.depth,
+                    frame.to.unwrap(),
+                    frame.success,
+                    frame.value,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn converts_call_tracer_traces() {
+        let traces = serde_json::from_str::<Vec<TransactionTrace>>(include_str!(
+            "fixtures/call_tracer.json"
+        ))
+        .unwrap();
+        let frames = call_tracer(traces);
+
+        assert_eq!(summary(&frames), expected());
+        assert_eq!(frames[0].input[..4], [0x13, 0xd7, 0x9a, 0x0b]);
+        assert_ne!(frames[0].transaction_hash, frames[6].transaction_hash);
+    }
+
+    #[test]
+    fn converts_parity_traces() {
+        let traces =
+            serde_json::from_str::<Vec<Trace>>(include_str!("fixtures/parity.json")).unwrap();
+        let frames = parity(traces);
+
+        assert_eq!(summary(&frames), expected());
+        assert_eq!(frames[0].input[..4], [0x13, 0xd7, 0x9a, 0x0b]);
+        assert_ne!(frames[0].transaction_hash, frames[6].transaction_hash);
+    }
+}
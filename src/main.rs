@@ -5,16 +5,42 @@ mod database;
 mod indexer;
 
 use {
-    self::{config::Config, indexer::Indexer},
+    self::{
+        config::Config,
+        indexer::{Failover, Indexer},
+    },
     anyhow::{Context, Result},
-    clap::Parser,
+    clap::{Parser, Subcommand},
+    futures::future,
     std::{env, path::PathBuf},
+    tracing::Instrument,
 };
 
 #[derive(Parser)]
 struct Arguments {
     #[clap(short, long, env = "ARAKCONFIG", default_value = "arak.toml")]
     config: PathBuf,
+    /// Exit once every event is indexed until its end block or the finalized
+    /// block, instead of continuing to sync new blocks.
+    #[clap(long)]
+    once: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Removes the indexed data of an event starting at a block, so that it
+    /// is indexed again the next time Arak runs. Arak must not be running.
+    Reindex {
+        /// The name of the event, call, transfer or snapshot, prefixed with
+        /// the network name for named networks.
+        #[clap(long)]
+        event: String,
+        /// The first block to reindex.
+        #[clap(long)]
+        from: u64,
+    },
 }
 
 #[tokio::main]
@@ -25,25 +51,100 @@ async fn main() -> Result<()> {
     let (config, root) = Config::load(&args.config).context("failed to load configuration")?;
     env::set_current_dir(root)?;
 
+    if let Some(Command::Reindex { event, .. }) = &args.command {
+        let network = config
+            .networks()
+            .into_iter()
+            .find(|network| network.names().contains(event))
+            .with_context(|| format!("unknown event {event}"))?;
+        return run_network(&config, network, &args).await;
+    }
+
+    // Every network is indexed concurrently by its own indexer, each with its
+    // own connection to the shared database.
+    future::try_join_all(config.networks().into_iter().map(|network| {
+        let span = tracing::info_span!("network", name = %network.name);
+        run_network(&config, network, &args).instrument(span)
+    }))
+    .await?;
+
+    Ok(())
+}
+
+async fn run_network(config: &Config, network: config::Network, args: &Arguments) -> Result<()> {
     match &config.database {
         config::Database::Sqlite { connection } => {
-            run_indexer(&config, database::Sqlite::open(connection)?).await?;
+            run_indexer(
+                config,
+                &network,
+                database::Sqlite::open(connection, &network.name)?,
+                args,
+            )
+            .await
         }
         config::Database::Postgres { connection } => {
-            run_indexer(&config, database::Postgres::connect(connection).await?).await?;
+            run_indexer(
+                config,
+                &network,
+                database::Postgres::connect(connection, &network.name).await?,
+                args,
+            )
+            .await
         }
     }
-
-    Ok(())
 }
 
-async fn run_indexer(config: &Config, db: impl Database) -> Result<()> {
-    let eth = ethrpc::http::Client::new(config.ethrpc.clone());
+async fn run_indexer(
+    config: &Config,
+    network: &config::Network,
+    db: impl Database,
+    args: &Arguments,
+) -> Result<()> {
+    let eth = Failover::new(
+        network
+            .ethrpc
+            .iter()
+            .map(|endpoint| ethrpc::http::Client::new(endpoint.url.clone()))
+            .collect(),
+    );
+    let settings = network.indexer.as_ref().unwrap_or(&config.indexer);
+
+    let mut indexer = Indexer::create(
+        eth,
+        db,
+        network.events(),
+        network.calls(),
+        network.transfers(),
+        network.snapshots(),
+    )?;
+    if let Some(Command::Reindex { event, from }) = &args.command {
+        return indexer.reindex(event, *from).await;
+    }
+    if let Some(url) = settings.websocket.as_ref().filter(|_| !args.once) {
+        indexer.subscribe(url.clone());
+    }
 
-    Indexer::create(eth, db, config.events.clone())?
+    indexer
         .run(indexer::Run {
-            page_size: config.indexer.page_size,
-            poll_interval: config.indexer.poll_interval,
+            page_size: settings.page_size,
+            concurrency: settings.concurrency,
+            poll_interval: settings.poll_interval,
+            finality: match settings.finality {
+                config::Finality::Finalized => indexer::Finality::Finalized,
+                config::Finality::Safe => indexer::Finality::Safe,
+                config::Finality::Confirmations(confirmations) => {
+                    indexer::Finality::Confirmations(confirmations)
+                }
+            },
+            retry: indexer::Retry {
+                initial_backoff: settings.retry.initial_backoff,
+                max_backoff: settings.retry.max_backoff,
+                max_retries: settings.retry.max_retries,
+            },
+            cross_check: settings.cross_check,
+            fatal_decode_errors: settings.fatal_decode_errors,
+            traces: settings.traces,
+            once: args.once,
         })
         .await?;
 